@@ -0,0 +1,335 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::sorting::heapsort::{heapify, sift_down, sift_up, sort_heap};
+
+/// A priority queue implemented with a binary max-heap.
+///
+/// The greatest element is always at the root, `data[0]`. Use
+/// [`std::cmp::Reverse`][] or a comparator via
+/// [`BinaryHeap::with_comparator`][] for a min-heap.
+///
+/// The heap shares its sift primitives with
+/// [`heapsort`](crate::sorting::heapsort).
+///
+/// ```
+/// use rust_algorithm_club::collections::BinaryHeap;
+/// use std::cmp::Reverse;
+///
+/// let mut heap = BinaryHeap::from(vec![Reverse(3), Reverse(1), Reverse(2)]);
+/// assert_eq!(heap.pop(), Some(Reverse(1)));
+/// ```
+///
+/// References:
+///
+/// - [Binary heap](https://en.wikipedia.org/wiki/Binary_heap)
+// ANCHOR: struct
+pub struct BinaryHeap<T, F = fn(&T, &T) -> Ordering> {
+    data: Vec<T>,
+    compare: F,
+}
+// ANCHOR_END: struct
+
+impl<T: Ord> BinaryHeap<T> {
+    /// Initialize an empty max-heap.
+    pub fn new() -> Self {
+        Self::with_comparator(T::cmp)
+    }
+}
+
+impl<T: Ord> Default for BinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build a heap from a vector in linear time.
+impl<T: Ord> From<Vec<T>> for BinaryHeap<T> {
+    // ANCHOR: from
+    fn from(mut data: Vec<T>) -> Self {
+        let mut compare: fn(&T, &T) -> Ordering = T::cmp;
+        heapify(&mut data, &mut compare);
+        Self { data, compare }
+    }
+    // ANCHOR_END: from
+}
+
+impl<T, F> BinaryHeap<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    /// Initialize an empty heap ordered by a comparator. The greatest
+    /// element by `compare` is popped first.
+    pub fn with_comparator(compare: F) -> Self {
+        Self {
+            data: Vec::new(),
+            compare,
+        }
+    }
+
+    /// Adds an element into the heap.
+    ///
+    /// # Complexity
+    ///
+    /// Logarithmic in the size of the heap.
+    // ANCHOR: push
+    pub fn push(&mut self, item: T) {
+        self.data.push(item);
+        let last = self.data.len() - 1;
+        sift_up(&mut self.data, last, &mut self.compare);
+    }
+    // ANCHOR_END: push
+
+    /// Removes the greatest element from the heap.
+    ///
+    /// # Complexity
+    ///
+    /// Logarithmic in the size of the heap.
+    // ANCHOR: pop
+    pub fn pop(&mut self) -> Option<T> {
+        let item = self.data.pop()?;
+        if self.data.is_empty() {
+            return Some(item);
+        }
+        // Put the last element at the root, and sift it down.
+        let root = std::mem::replace(&mut self.data[0], item);
+        sift_down(&mut self.data, 0, &mut self.compare);
+        Some(root)
+    }
+    // ANCHOR_END: pop
+
+    /// Retrieves the greatest element without removing it.
+    ///
+    /// # Complexity
+    ///
+    /// Constant.
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    /// Retrieves a mutable reference to the greatest element. The heap is
+    /// fixed when the returned guard is dropped.
+    ///
+    /// # Complexity
+    ///
+    /// Logarithmic in the size of the heap if the element is modified.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, F>> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(PeekMut { heap: self })
+        }
+    }
+
+    /// Moves all elements of `other` into `self`, leaving `other` empty.
+    ///
+    /// # Complexity
+    ///
+    /// O(m log(n + m)) for pushing `m` elements one by one, or O(n + m) for
+    /// rebuilding the heap, whichever is smaller.
+    pub fn append(&mut self, other: &mut Self) {
+        // Elements of `other` are ordered by its own comparator, which may
+        // differ even if the type is the same, so they are always re-sifted.
+        let (len, other_len) = (self.len(), other.len());
+        let log = (usize::BITS - (len + other_len).leading_zeros()) as usize;
+        if other_len * log < 2 * (len + other_len) {
+            for item in other.data.drain(..) {
+                self.data.push(item);
+                let last = self.data.len() - 1;
+                sift_up(&mut self.data, last, &mut self.compare);
+            }
+        } else {
+            self.data.append(&mut other.data);
+            heapify(&mut self.data, &mut self.compare);
+        }
+    }
+
+    /// Consumes the heap and returns a vector in ascending order, sorted in
+    /// place by heapsort.
+    ///
+    /// # Complexity
+    ///
+    /// O(n log n).
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        sort_heap(&mut self.data, &mut self.compare);
+        self.data
+    }
+
+    /// Consumes the heap and returns the underlying vector in heap order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Retrieves the size of the heap.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for BinaryHeap<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.data.iter()).finish()
+    }
+}
+
+/// A mutable reference to the greatest element of a `BinaryHeap`, returned
+/// by `peek_mut`. The element is sifted down when dropped.
+pub struct PeekMut<'a, T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    heap: &'a mut BinaryHeap<T, F>,
+}
+
+impl<T, F> Deref for PeekMut<'_, T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.heap.data[0]
+    }
+}
+
+impl<T, F> DerefMut for PeekMut<'_, T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.heap.data[0]
+    }
+}
+
+impl<T, F> Drop for PeekMut<'_, T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    fn drop(&mut self) {
+        let heap = &mut *self.heap;
+        sift_down(&mut heap.data, 0, &mut heap.compare);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Reverse;
+
+    /// No child is greater than its parent.
+    fn is_heap<T, F>(heap: &BinaryHeap<T, F>, mut compare: impl FnMut(&T, &T) -> Ordering) -> bool {
+        (1..heap.data.len())
+            .all(|i| compare(&heap.data[(i - 1) / 2], &heap.data[i]) != Ordering::Less)
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let mut heap = BinaryHeap::new();
+        assert_eq!(heap.pop(), None);
+        let arr: Vec<_> = (0..500).map(|_| rand::random::<i32>() % 50).collect();
+        for &v in &arr {
+            heap.push(v);
+            assert!(is_heap(&heap, i32::cmp));
+        }
+        assert_eq!(heap.len(), arr.len());
+        let mut res = arr;
+        res.sort_by(|a, b| b.cmp(a));
+        for v in res {
+            assert_eq!(heap.peek(), Some(&v));
+            assert_eq!(heap.pop(), Some(v));
+            assert!(is_heap(&heap, i32::cmp));
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn from_vec() {
+        let arr: Vec<_> = (0..1000).map(|_| rand::random::<i32>()).collect();
+        let heap = BinaryHeap::from(arr.clone());
+        assert!(is_heap(&heap, i32::cmp));
+        let mut res = arr;
+        res.sort();
+        assert_eq!(heap.into_sorted_vec(), res);
+    }
+
+    #[test]
+    fn linear_heapify() {
+        let arr: Vec<_> = (0..10000).collect();
+        let mut heap = BinaryHeap::new();
+        let stats = crate::sorting::SortStats::collect(|| heap = BinaryHeap::from(arr));
+        // At most n swaps for building a heap bottom-up.
+        assert!(stats.swaps <= 10000);
+    }
+
+    #[test]
+    fn min_heap() {
+        let mut heap = BinaryHeap::new();
+        let mut by_comparator = BinaryHeap::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        for &v in &[5, 1, 8, 3, 2] {
+            heap.push(Reverse(v));
+            by_comparator.push(v);
+        }
+        assert_eq!(heap.pop(), Some(Reverse(1)));
+        assert_eq!(by_comparator.pop(), Some(1));
+        assert_eq!(by_comparator.into_sorted_vec(), [8, 5, 3, 2]);
+    }
+
+    #[test]
+    fn peek_mut() {
+        let mut heap = BinaryHeap::from(vec![1, 5, 2]);
+        assert_eq!(heap.peek(), Some(&5));
+        if let Some(mut top) = heap.peek_mut() {
+            *top = 0;
+        }
+        assert!(is_heap(&heap, i32::cmp));
+        assert_eq!(heap.peek(), Some(&2));
+        assert!(BinaryHeap::<i32>::new().peek_mut().is_none());
+    }
+
+    #[test]
+    fn append() {
+        for &(m, n) in &[(0, 0), (10, 0), (0, 10), (3, 100), (100, 3), (50, 50)] {
+            let a: Vec<_> = (0..m).map(|_| rand::random::<i32>()).collect();
+            let b: Vec<_> = (0..n).map(|_| rand::random::<i32>()).collect();
+            let mut heap = BinaryHeap::from(a.clone());
+            let mut other = BinaryHeap::from(b.clone());
+            heap.append(&mut other);
+            assert!(other.is_empty());
+            assert!(is_heap(&heap, i32::cmp));
+            let mut res = [a, b].concat();
+            res.sort();
+            assert_eq!(heap.into_sorted_vec(), res);
+        }
+    }
+
+    #[test]
+    fn append_other_comparator() {
+        fn rev(a: &i32, b: &i32) -> Ordering {
+            b.cmp(a)
+        }
+        let mut heap = BinaryHeap::from(vec![1, 50]);
+        let mut other = BinaryHeap::with_comparator(rev as fn(&i32, &i32) -> Ordering);
+        for v in 60..100 {
+            other.push(v);
+        }
+        heap.append(&mut other);
+        assert!(is_heap(&heap, i32::cmp));
+        assert_eq!(heap.pop(), Some(99));
+    }
+
+    #[test]
+    fn non_clone_items() {
+        let mut heap = BinaryHeap::new();
+        for v in &[3, 1, 2] {
+            heap.push(Box::new(*v));
+        }
+        assert_eq!(heap.pop(), Some(Box::new(3)));
+        assert_eq!(format!("{:?}", heap), "[2, 1]");
+    }
+}
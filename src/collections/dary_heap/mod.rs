@@ -0,0 +1,208 @@
+/// A priority queue implemented with a d-ary max-heap.
+///
+/// Like [`BinaryHeap`](crate::collections::BinaryHeap), but each node has
+/// `D` children. A wider node makes the tree shallower, so `push` is faster
+/// and `pop` compares more children per level. Children of a node are
+/// adjacent in memory, which is friendly to cache. A 4-ary heap often beats
+/// a binary heap in practice, such as in Dijkstra's algorithm where pushes
+/// outnumber pops.
+///
+/// Use [`std::cmp::Reverse`][] for a min-heap.
+///
+/// ```
+/// use rust_algorithm_club::collections::DaryHeap;
+///
+/// let mut heap = DaryHeap::<_, 4>::new();
+/// heap.push(3);
+/// heap.push(7);
+/// heap.push(5);
+/// assert_eq!(heap.pop(), Some(7));
+/// ```
+///
+/// References:
+///
+/// - [d-ary heap](https://en.wikipedia.org/wiki/D-ary_heap)
+// ANCHOR: struct
+pub struct DaryHeap<T, const D: usize> {
+    data: Vec<T>,
+}
+// ANCHOR_END: struct
+
+impl<T: Ord, const D: usize> DaryHeap<T, D> {
+    /// Initialize an empty heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `D` is less than 2.
+    pub fn new() -> Self {
+        assert!(D >= 2, "a d-ary heap needs at least 2 children per node");
+        Self { data: Vec::new() }
+    }
+
+    /// Adds an element into the heap.
+    ///
+    /// # Complexity
+    ///
+    /// O(log_d n).
+    pub fn push(&mut self, item: T) {
+        self.data.push(item);
+        self.sift_up(self.data.len() - 1);
+    }
+
+    /// Removes the greatest element from the heap.
+    ///
+    /// # Complexity
+    ///
+    /// O(d log_d n).
+    pub fn pop(&mut self) -> Option<T> {
+        let item = self.data.pop()?;
+        if self.data.is_empty() {
+            return Some(item);
+        }
+        let root = std::mem::replace(&mut self.data[0], item);
+        self.sift_down(0);
+        Some(root)
+    }
+
+    /// Retrieves the greatest element without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    /// Retrieves the size of the heap.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Consumes the heap and returns a vector in ascending order.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut sorted = Vec::with_capacity(self.len());
+        while let Some(item) = self.pop() {
+            sorted.push(item);
+        }
+        sorted.reverse();
+        sorted
+    }
+
+    /// Move the element at `pos` up until its parent is not less than it.
+    // ANCHOR: sift_up
+    fn sift_up(&mut self, mut pos: usize) {
+        while pos > 0 {
+            let parent = (pos - 1) / D;
+            if self.data[parent] >= self.data[pos] {
+                break;
+            }
+            self.data.swap(parent, pos);
+            pos = parent;
+        }
+    }
+    // ANCHOR_END: sift_up
+
+    /// Move the element at `pos` down until no child is greater than it.
+    // ANCHOR: sift_down
+    fn sift_down(&mut self, mut pos: usize) {
+        let len = self.data.len();
+        loop {
+            let first = pos * D + 1;
+            if first >= len {
+                break;
+            }
+            // The greatest of at most `D` children.
+            let last = (first + D).min(len);
+            let child = (first + 1..last).fold(first, |max, c| {
+                if self.data[c] > self.data[max] {
+                    c
+                } else {
+                    max
+                }
+            });
+            if self.data[pos] >= self.data[child] {
+                break;
+            }
+            self.data.swap(pos, child);
+            pos = child;
+        }
+    }
+    // ANCHOR_END: sift_down
+}
+
+impl<T: Ord, const D: usize> Default for DaryHeap<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Build a heap from a vector in linear time.
+impl<T: Ord, const D: usize> From<Vec<T>> for DaryHeap<T, D> {
+    fn from(data: Vec<T>) -> Self {
+        let mut heap = Self::new();
+        heap.data = data;
+        // Sift down every non-leaf node, from the last one to the root.
+        let len = heap.data.len();
+        if len > 1 {
+            for pos in (0..=(len - 2) / D).rev() {
+                heap.sift_down(pos);
+            }
+        }
+        heap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Reverse;
+
+    fn is_heap<T: Ord, const D: usize>(heap: &DaryHeap<T, D>) -> bool {
+        (1..heap.data.len()).all(|i| heap.data[(i - 1) / D] >= heap.data[i])
+    }
+
+    fn push_and_pop<const D: usize>() {
+        let arr: Vec<_> = (0..500).map(|_| rand::random::<i32>() % 50).collect();
+        let mut heap = DaryHeap::<_, D>::new();
+        for &v in &arr {
+            heap.push(v);
+            assert!(is_heap(&heap));
+        }
+        let mut res = arr.clone();
+        res.sort_by(|a, b| b.cmp(a));
+        for v in res {
+            assert_eq!(heap.peek(), Some(&v));
+            assert_eq!(heap.pop(), Some(v));
+            assert!(is_heap(&heap));
+        }
+        assert_eq!(heap.pop(), None);
+
+        let heap = DaryHeap::<_, D>::from(arr.clone());
+        assert!(is_heap(&heap));
+        let mut res = arr;
+        res.sort();
+        assert_eq!(heap.into_sorted_vec(), res);
+    }
+
+    #[test]
+    fn arities() {
+        push_and_pop::<2>();
+        push_and_pop::<3>();
+        push_and_pop::<4>();
+        push_and_pop::<8>();
+    }
+
+    #[test]
+    fn min_heap() {
+        let mut heap = DaryHeap::<_, 4>::from(vec![Reverse(5), Reverse(1), Reverse(3)]);
+        assert_eq!(heap.pop(), Some(Reverse(1)));
+        assert_eq!(heap.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 2 children")]
+    fn unary() {
+        DaryHeap::<i32, 1>::new();
+    }
+}
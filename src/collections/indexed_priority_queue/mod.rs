@@ -0,0 +1,329 @@
+use crate::collections::HashMap;
+use std::hash::Hash;
+
+/// An indexed min-priority queue.
+///
+/// Each entry is a key with a priority, and the key is the handle to look
+/// up, update or remove its entry. Entries are kept in a binary min-heap,
+/// with a hash map from each key to its position in the heap, so finding an
+/// entry is O(1) and updating it is O(log n).
+///
+/// This is what Dijkstra's and Prim's algorithms need: a vertex is a key,
+/// and its tentative distance is the priority, which decreases as shorter
+/// paths are found.
+///
+/// ```
+/// use rust_algorithm_club::collections::IndexedPriorityQueue;
+///
+/// let mut queue = IndexedPriorityQueue::new();
+/// queue.push("a", 5);
+/// queue.push("b", 3);
+/// queue.decrease_key(&"a", 1);
+/// assert_eq!(queue.pop(), Some(("a", 1)));
+/// ```
+///
+/// References:
+///
+/// - [Priority queue](https://en.wikipedia.org/wiki/Priority_queue)
+// ANCHOR: struct
+pub struct IndexedPriorityQueue<K, P>
+where
+    K: Hash + Eq + Clone,
+    P: Ord,
+{
+    heap: Vec<(K, P)>,
+    positions: HashMap<K, usize>,
+}
+// ANCHOR_END: struct
+
+impl<K, P> IndexedPriorityQueue<K, P>
+where
+    K: Hash + Eq + Clone,
+    P: Ord,
+{
+    /// Initialize an empty queue.
+    pub fn new() -> Self {
+        Self {
+            heap: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Adds a key with a priority, or updates the priority of an existing
+    /// key. Returns the old priority if the key exists.
+    ///
+    /// # Complexity
+    ///
+    /// O(log n).
+    pub fn push(&mut self, key: K, priority: P) -> Option<P> {
+        if let Some(&pos) = self.positions.get(&key) {
+            let old = std::mem::replace(&mut self.heap[pos].1, priority);
+            self.fix(pos);
+            return Some(old);
+        }
+        let pos = self.heap.len();
+        self.positions.insert(key.clone(), pos);
+        self.heap.push((key, priority));
+        self.sift_up(pos);
+        None
+    }
+
+    /// Removes the entry with the smallest priority.
+    ///
+    /// # Complexity
+    ///
+    /// O(log n).
+    pub fn pop(&mut self) -> Option<(K, P)> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Retrieves the entry with the smallest priority without removing it.
+    pub fn peek(&self) -> Option<(&K, &P)> {
+        self.heap.first().map(|(k, p)| (k, p))
+    }
+
+    /// Retrieves the priority of a key.
+    pub fn priority(&self, key: &K) -> Option<&P> {
+        self.positions.get(key).map(|&pos| &self.heap[pos].1)
+    }
+
+    /// Whether a key is in the queue.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.positions.get(key).is_some()
+    }
+
+    /// Lowers the priority of a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key does not exist, or `priority` is greater than the
+    /// current priority.
+    // ANCHOR: decrease_key
+    pub fn decrease_key(&mut self, key: &K, priority: P) {
+        let pos = *self.positions.get(key).expect("key not found");
+        assert!(
+            priority <= self.heap[pos].1,
+            "new priority is greater than current priority"
+        );
+        self.heap[pos].1 = priority;
+        self.sift_up(pos);
+    }
+    // ANCHOR_END: decrease_key
+
+    /// Raises the priority of a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key does not exist, or `priority` is less than the
+    /// current priority.
+    pub fn increase_key(&mut self, key: &K, priority: P) {
+        let pos = *self.positions.get(key).expect("key not found");
+        assert!(
+            priority >= self.heap[pos].1,
+            "new priority is less than current priority"
+        );
+        self.heap[pos].1 = priority;
+        self.sift_down(pos);
+    }
+
+    /// Removes a key and returns its priority.
+    ///
+    /// # Complexity
+    ///
+    /// O(log n).
+    pub fn remove(&mut self, key: &K) -> Option<P> {
+        let pos = *self.positions.get(key)?;
+        Some(self.remove_at(pos).1)
+    }
+
+    /// Retrieves the size of the queue.
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Remove the entry at `pos` by replacing it with the last entry.
+    // ANCHOR: remove_at
+    fn remove_at(&mut self, pos: usize) -> (K, P) {
+        let last = self.heap.len() - 1;
+        self.swap(pos, last);
+        let entry = self.heap.pop().unwrap();
+        self.positions.remove(&entry.0);
+        if pos < self.heap.len() {
+            self.fix(pos);
+        }
+        entry
+    }
+    // ANCHOR_END: remove_at
+
+    /// Restore heap order of the entry at `pos` in either direction.
+    // ANCHOR: fix
+    fn fix(&mut self, pos: usize) {
+        let pos = self.sift_up(pos);
+        self.sift_down(pos);
+    }
+    // ANCHOR_END: fix
+
+    /// Swap two entries and update their positions.
+    // ANCHOR: swap
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        *self.positions.get_mut(&self.heap[a].0).unwrap() = a;
+        *self.positions.get_mut(&self.heap[b].0).unwrap() = b;
+    }
+    // ANCHOR_END: swap
+
+    /// Move the entry at `pos` up while it is less than its parent. Returns
+    /// its final position.
+    fn sift_up(&mut self, mut pos: usize) -> usize {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.heap[parent].1 <= self.heap[pos].1 {
+                break;
+            }
+            self.swap(parent, pos);
+            pos = parent;
+        }
+        pos
+    }
+
+    /// Move the entry at `pos` down while a child is less than it.
+    fn sift_down(&mut self, mut pos: usize) {
+        let len = self.heap.len();
+        loop {
+            let mut child = pos * 2 + 1;
+            if child >= len {
+                break;
+            }
+            if child + 1 < len && self.heap[child + 1].1 < self.heap[child].1 {
+                child += 1;
+            }
+            if self.heap[pos].1 <= self.heap[child].1 {
+                break;
+            }
+            self.swap(pos, child);
+            pos = child;
+        }
+    }
+}
+
+impl<K, P> Default for IndexedPriorityQueue<K, P>
+where
+    K: Hash + Eq + Clone,
+    P: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// No child is less than its parent, and every position is indexed.
+    fn assert_valid(queue: &IndexedPriorityQueue<u32, i32>) {
+        for (i, (key, priority)) in queue.heap.iter().enumerate() {
+            assert_eq!(queue.positions.get(key), Some(&i));
+            if i > 0 {
+                assert!(queue.heap[(i - 1) / 2].1 <= *priority);
+            }
+        }
+        assert_eq!(queue.positions.len(), queue.heap.len());
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let mut queue = IndexedPriorityQueue::new();
+        assert_eq!(queue.pop(), None);
+        let priorities: Vec<i32> = (0..300).map(|_| rand::random::<i32>() % 50).collect();
+        for (key, &p) in priorities.iter().enumerate() {
+            assert_eq!(queue.push(key as u32, p), None);
+            assert_valid(&queue);
+        }
+        let mut res = priorities;
+        res.sort();
+        for p in res {
+            assert_eq!(queue.peek().map(|(_, p)| *p), Some(p));
+            let (key, popped) = queue.pop().unwrap();
+            assert_eq!(popped, p);
+            assert!(!queue.contains_key(&key));
+            assert_valid(&queue);
+        }
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn update_by_key() {
+        let mut queue = IndexedPriorityQueue::new();
+        for key in 0..100u32 {
+            queue.push(key, key as i32 * 10);
+        }
+        queue.decrease_key(&50, -1);
+        assert_valid(&queue);
+        assert_eq!(queue.peek(), Some((&50, &-1)));
+        queue.increase_key(&50, 2000);
+        queue.increase_key(&0, 5);
+        assert_valid(&queue);
+        assert_eq!(queue.peek(), Some((&0, &5)));
+        assert_eq!(queue.push(0, 15), Some(5));
+        assert_eq!(queue.priority(&0), Some(&15));
+        assert_eq!(queue.remove(&1), Some(10));
+        assert_eq!(queue.remove(&1), None);
+        assert_valid(&queue);
+        assert_eq!(queue.pop(), Some((0, 15)));
+        assert_eq!(queue.pop(), Some((2, 20)));
+    }
+
+    #[test]
+    fn random_operations() {
+        let mut queue = IndexedPriorityQueue::new();
+        let mut model = std::collections::BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rand::random::<u32>() % 64;
+            let priority = rand::random::<i32>() % 1000;
+            match rand::random::<u8>() % 4 {
+                0 => assert_eq!(queue.push(key, priority), model.insert(key, priority)),
+                1 => assert_eq!(queue.remove(&key), model.remove(&key)),
+                2 => {
+                    if let Some(old) = model.get_mut(&key) {
+                        let p = priority.min(*old);
+                        queue.decrease_key(&key, p);
+                        *old = p;
+                    }
+                }
+                _ => {
+                    let min = model.values().min().copied();
+                    let popped = queue.pop();
+                    assert_eq!(popped.as_ref().map(|e| e.1), min);
+                    if let Some((key, _)) = popped {
+                        model.remove(&key);
+                    }
+                }
+            }
+            assert_valid(&queue);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than current")]
+    fn decrease_to_greater() {
+        let mut queue = IndexedPriorityQueue::new();
+        queue.push("a", 1);
+        queue.decrease_key(&"a", 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found")]
+    fn increase_missing() {
+        let mut queue: IndexedPriorityQueue<&str, i32> = IndexedPriorityQueue::new();
+        queue.increase_key(&"a", 2);
+    }
+}
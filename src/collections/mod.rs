@@ -20,3 +20,15 @@ pub use self::deque::Deque;
 
 mod queue;
 pub use self::queue::Queue;
+
+mod binary_heap;
+pub use self::binary_heap::{BinaryHeap, PeekMut};
+
+mod dary_heap;
+pub use self::dary_heap::DaryHeap;
+
+mod pairing_heap;
+pub use self::pairing_heap::PairingHeap;
+
+mod indexed_priority_queue;
+pub use self::indexed_priority_queue::IndexedPriorityQueue;
@@ -0,0 +1,209 @@
+/// A priority queue implemented with a pairing heap.
+///
+/// A pairing heap is a tree where no child is greater than its parent.
+/// Melding two heaps just makes the root with the smaller element a child of
+/// the other, so `push` and `meld` are O(1). `pop` removes the root and
+/// melds its children in pairs, in O(log n) amortized time.
+///
+/// Use [`std::cmp::Reverse`][] for a min-heap.
+///
+/// ```
+/// use rust_algorithm_club::collections::PairingHeap;
+///
+/// let mut a: PairingHeap<_> = vec![1, 5].into_iter().collect();
+/// let b: PairingHeap<_> = vec![3, 7].into_iter().collect();
+/// a.meld(b);
+/// assert_eq!(a.pop(), Some(7));
+/// ```
+///
+/// References:
+///
+/// - [Pairing heap](https://en.wikipedia.org/wiki/Pairing_heap)
+/// - Fredman, Sedgewick, Sleator and Tarjan, "The pairing heap: A new form
+///   of self-adjusting heap".
+// ANCHOR: struct
+pub struct PairingHeap<T> {
+    root: Option<Box<Node<T>>>,
+    len: usize,
+}
+
+struct Node<T> {
+    item: T,
+    children: Vec<Box<Node<T>>>,
+}
+// ANCHOR_END: struct
+
+impl<T: Ord> PairingHeap<T> {
+    /// Initialize an empty heap.
+    pub fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    /// Adds an element into the heap.
+    ///
+    /// # Complexity
+    ///
+    /// Constant.
+    pub fn push(&mut self, item: T) {
+        let node = Box::new(Node {
+            item,
+            children: Vec::new(),
+        });
+        self.root = Self::meld_nodes(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    /// Removes the greatest element from the heap.
+    ///
+    /// # Complexity
+    ///
+    /// O(log n) amortized.
+    // ANCHOR: pop
+    pub fn pop(&mut self) -> Option<T> {
+        let root = self.root.take()?;
+        let Node { item, children } = *root;
+        self.root = Self::merge_pairs(children);
+        self.len -= 1;
+        Some(item)
+    }
+    // ANCHOR_END: pop
+
+    /// Retrieves the greatest element without removing it.
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.item)
+    }
+
+    /// Moves all elements of `other` into `self`.
+    ///
+    /// # Complexity
+    ///
+    /// Constant.
+    pub fn meld(&mut self, mut other: Self) {
+        self.root = Self::meld_nodes(self.root.take(), other.root.take());
+        self.len += other.len;
+    }
+
+    /// Retrieves the size of the heap.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the heap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The root with the smaller element becomes a child of the other.
+    // ANCHOR: meld_nodes
+    fn meld_nodes(a: Option<Box<Node<T>>>, b: Option<Box<Node<T>>>) -> Option<Box<Node<T>>> {
+        match (a, b) {
+            (Some(mut a), Some(b)) if a.item >= b.item => {
+                a.children.push(b);
+                Some(a)
+            }
+            (Some(a), Some(mut b)) => {
+                b.children.push(a);
+                Some(b)
+            }
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+    // ANCHOR_END: meld_nodes
+
+    /// Two-pass pairing: meld children in pairs from left to right, then
+    /// meld the pairs from right to left.
+    // ANCHOR: merge_pairs
+    fn merge_pairs(children: Vec<Box<Node<T>>>) -> Option<Box<Node<T>>> {
+        let mut pairs = Vec::with_capacity(children.len() / 2 + 1);
+        let mut children = children.into_iter();
+        while let Some(a) = children.next() {
+            pairs.push(Self::meld_nodes(Some(a), children.next()));
+        }
+        pairs
+            .into_iter()
+            .rev()
+            .fold(None, |acc, pair| Self::meld_nodes(pair, acc))
+    }
+    // ANCHOR_END: merge_pairs
+}
+
+impl<T: Ord> Default for PairingHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> std::iter::FromIterator<T> for PairingHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = Self::new();
+        for item in iter {
+            heap.push(item);
+        }
+        heap
+    }
+}
+
+/// Drop nodes iteratively. The tree can be as deep as the number of
+/// elements, which overflows the stack if dropped recursively.
+// ANCHOR: drop
+impl<T> Drop for PairingHeap<T> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+// ANCHOR_END: drop
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Reverse;
+
+    #[test]
+    fn push_and_pop() {
+        let arr: Vec<_> = (0..1000).map(|_| rand::random::<i32>() % 50).collect();
+        let mut heap = PairingHeap::new();
+        assert_eq!(heap.pop(), None);
+        for &v in &arr {
+            heap.push(v);
+        }
+        assert_eq!(heap.len(), arr.len());
+        let mut res = arr;
+        res.sort_by(|a, b| b.cmp(a));
+        for v in res {
+            assert_eq!(heap.peek(), Some(&v));
+            assert_eq!(heap.pop(), Some(v));
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn meld() {
+        let mut a: PairingHeap<_> = (0..100).step_by(2).collect();
+        let b: PairingHeap<_> = (1..100).step_by(2).collect();
+        a.meld(b);
+        a.meld(PairingHeap::new());
+        assert_eq!(a.len(), 100);
+        for v in (0..100).rev() {
+            assert_eq!(a.pop(), Some(v));
+        }
+    }
+
+    #[test]
+    fn min_heap() {
+        let mut heap: PairingHeap<_> = vec![Reverse(3), Reverse(1), Reverse(2)]
+            .into_iter()
+            .collect();
+        assert_eq!(heap.pop(), Some(Reverse(1)));
+    }
+
+    #[test]
+    fn drop_deep_tree() {
+        // Ascending pushes build a chain as deep as the number of elements.
+        let heap: PairingHeap<_> = (0..1_000_000).collect();
+        drop(heap);
+    }
+}
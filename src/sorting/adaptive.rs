@@ -0,0 +1,601 @@
+//! Adaptive sorting, choosing an algorithm from input characteristics.
+
+use std::any::TypeId;
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::sorting::introsort::median_of_three;
+use crate::sorting::measures::inversions_by;
+use crate::sorting::quicksort::partition_3way;
+use crate::sorting::stats::{self, swap};
+use crate::sorting::{heapsort_by, insertion_sort_by, mergesort_by, radix_sort};
+
+/// Slices no longer than this are sorted by insertion sort.
+const SMALL: usize = 32;
+
+/// Number of elements sampled to estimate presortedness and duplicates.
+const SAMPLE: usize = 64;
+
+/// Integer slices shorter than this are not worth radix sort passes.
+const RADIX_MIN: usize = 256;
+
+/// Insertion sort chosen for presorted input gives up after this many swaps
+/// per element, since sampling cannot rule out many inversions.
+const INSERTION_SWAPS_PER_ELEMENT: usize = 4;
+
+/// Algorithms `sort` can choose.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// `insertion_sort`, for small or nearly sorted input.
+    InsertionSort,
+    /// `radix_sort`, for integers.
+    RadixSort,
+    /// `quicksort_3way`, for input with many duplicates. Pivots are medians
+    /// of three, and deeply nested partitions fall back to heapsort as in
+    /// introsort, since sampling may overestimate duplicates.
+    Quicksort3Way,
+    /// `mergesort`, the stable general-purpose choice.
+    Mergesort,
+    /// `heapsort`, the in-place general-purpose choice.
+    Heapsort,
+}
+
+impl Algorithm {
+    /// Whether equal elements keep their order.
+    pub fn is_stable(self) -> bool {
+        !matches!(self, Algorithm::Quicksort3Way | Algorithm::Heapsort)
+    }
+
+    /// Whether no auxiliary buffer proportional to the input is allocated.
+    pub fn is_in_place(self) -> bool {
+        !matches!(self, Algorithm::RadixSort | Algorithm::Mergesort)
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Algorithm::InsertionSort => "insertion_sort",
+            Algorithm::RadixSort => "radix_sort",
+            Algorithm::Quicksort3Way => "quicksort_3way",
+            Algorithm::Mergesort => "mergesort",
+            Algorithm::Heapsort => "heapsort",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How `sort` chooses an algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortPolicy {
+    /// Choose by input characteristics. The result may be unstable.
+    Auto,
+    /// Like `Auto`, but only stable algorithms.
+    Stable,
+    /// Like `Auto`, but only algorithms without an O(n) buffer.
+    InPlace,
+    /// Always use the given algorithm. Radix sort falls back to `Auto` for
+    /// non-integer elements.
+    Force(Algorithm),
+}
+
+/// Input characteristics measured before sorting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Characteristics {
+    /// Number of elements.
+    pub len: usize,
+    /// Number of adjacent pairs out of order, over the whole input.
+    pub descents: usize,
+    /// Number of elements sampled at evenly spaced positions.
+    pub sample_len: usize,
+    /// Inversions among sampled elements.
+    pub sample_inversions: usize,
+    /// Distinct values among sampled elements.
+    pub sample_distinct: usize,
+    /// Whether elements are primitive integers, sortable by radix sort.
+    pub integer: bool,
+}
+
+/// Which algorithm was chosen and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// The chosen algorithm.
+    pub algorithm: Algorithm,
+    /// Why it was chosen.
+    pub reason: String,
+    /// Characteristics the choice was based on.
+    pub characteristics: Characteristics,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.algorithm, self.reason)
+    }
+}
+
+/// Sort with an algorithm chosen from input characteristics.
+///
+/// - Small input: insertion sort.
+/// - Sorted or nearly sorted input: insertion sort, which is O(n + inversions).
+/// - Integers: radix sort.
+/// - Many duplicates: 3-way quicksort, which skips equal elements.
+/// - Otherwise: mergesort.
+///
+/// The result may be unstable. Use `sort_with` with `SortPolicy::Stable`
+/// for a stable sort.
+///
+/// Elements are required to be `'static` to tell integers from other types.
+/// Use `sort_by(arr, T::cmp)` for borrowed elements.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{explain_sort, sort, SortPolicy};
+///
+/// let mut arr: Vec<u32> = (0..1000).rev().collect();
+/// println!("{}", explain_sort(&arr, SortPolicy::Auto));
+/// sort(&mut arr);
+/// assert_eq!(arr, (0..1000).collect::<Vec<_>>());
+/// ```
+pub fn sort<T: Ord + 'static>(arr: &mut [T]) {
+    sort_with(arr, SortPolicy::Auto);
+}
+
+/// Sort with an algorithm chosen by `policy`, and explain the choice.
+pub fn sort_with<T: Ord + 'static>(arr: &mut [T], policy: SortPolicy) -> Explanation {
+    let mut compare = stats::counting(T::cmp);
+    let mut explanation = choose(arr, policy, is_integer::<T>(), &mut compare);
+    if explanation.algorithm == Algorithm::RadixSort {
+        let sorted = radix_sort_integers(arr);
+        debug_assert!(sorted);
+    } else {
+        run(arr, &mut explanation, policy, compare);
+    }
+    explanation
+}
+
+/// Explain which algorithm `sort_with` would choose, without sorting.
+///
+/// When insertion sort is chosen for presorted input, `sort_with` may still
+/// fall back to another algorithm if the input turns out to have too many
+/// inversions.
+pub fn explain_sort<T: Ord + 'static>(arr: &[T], policy: SortPolicy) -> Explanation {
+    choose(arr, policy, is_integer::<T>(), &mut T::cmp)
+}
+
+/// Sort with a comparator function and an algorithm chosen from input
+/// characteristics. Radix sort is never chosen.
+pub fn sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_with_by(arr, SortPolicy::Auto, compare);
+}
+
+/// Sort with a key extraction function and an algorithm chosen from input
+/// characteristics.
+pub fn sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Sort with a comparator function and an algorithm chosen by `policy`,
+/// and explain the choice.
+pub fn sort_with_by<T, F>(arr: &mut [T], policy: SortPolicy, compare: F) -> Explanation
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let mut explanation = choose(arr, policy, false, &mut compare);
+    run(arr, &mut explanation, policy, compare);
+    explanation
+}
+
+/// Run the chosen comparison sort.
+///
+/// Unless forced, insertion sort is given a budget of swaps linear in the
+/// length. Once exceeded, the input has too many inversions, and the rest
+/// of the work is handed to mergesort, or heapsort for `InPlace`. The
+/// explanation is updated accordingly.
+fn run<T, F>(arr: &mut [T], explanation: &mut Explanation, policy: SortPolicy, mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let algorithm = explanation.algorithm;
+    if algorithm == Algorithm::InsertionSort && !matches!(policy, SortPolicy::Force(_)) {
+        let budget = INSERTION_SWAPS_PER_ELEMENT * arr.len() + SMALL * SMALL / 2;
+        if insertion_sort_within(arr, budget, &mut compare) {
+            return;
+        }
+        let fallback = if policy == SortPolicy::InPlace {
+            Algorithm::Heapsort
+        } else {
+            Algorithm::Mergesort
+        };
+        explanation.algorithm = fallback;
+        explanation.reason = format!(
+            "{}, but more than {} swaps were needed, fell back to {}",
+            explanation.reason, budget, fallback
+        );
+        run(arr, explanation, policy, compare);
+        return;
+    }
+    match algorithm {
+        Algorithm::InsertionSort => insertion_sort_by(arr, compare),
+        Algorithm::Quicksort3Way => {
+            let depth_limit = 2 * (usize::BITS - arr.len().leading_zeros()) as usize;
+            quicksort_3way_within(arr, depth_limit, &mut compare);
+        }
+        Algorithm::Mergesort => mergesort_by(arr, compare),
+        Algorithm::Heapsort => heapsort_by(arr, compare),
+        Algorithm::RadixSort => unreachable!("radix sort needs integer keys"),
+    }
+}
+
+/// Insertion sort giving up after `budget` swaps. Returns whether sorted.
+fn insertion_sort_within<T, F>(arr: &mut [T], mut budget: usize, compare: &mut F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    for i in 1..arr.len() {
+        let mut j = i;
+        while j > 0 && compare(&arr[j - 1], &arr[j]) == Ordering::Greater {
+            if budget == 0 {
+                return false;
+            }
+            budget -= 1;
+            swap(arr, j - 1, j);
+            j -= 1;
+        }
+    }
+    true
+}
+
+/// 3-way quicksort switching to heapsort after `depth_limit` nested
+/// partitions, so its worst case is O(n log n).
+fn quicksort_3way_within<T, F>(mut arr: &mut [T], mut depth_limit: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let len = arr.len();
+        if len <= SMALL {
+            insertion_sort_by(arr, &mut *compare);
+            return;
+        }
+        if depth_limit == 0 {
+            heapsort_by(arr, &mut *compare);
+            return;
+        }
+        depth_limit -= 1;
+
+        // Move the median of three to the back, where `partition_3way`
+        // takes its pivot.
+        median_of_three(arr, 0, len / 2, len - 1, compare);
+        swap(arr, len / 2, len - 1);
+        let (smaller, larger) = partition_3way(arr, 0, len as isize - 1, compare);
+
+        // Elements equal to the pivot are done. Recurse into the smaller
+        // part, and loop on the larger one.
+        let (left, rest) = arr.split_at_mut(smaller as usize);
+        let right = &mut rest[(larger - smaller) as usize + 1..];
+        if left.len() < right.len() {
+            quicksort_3way_within(left, depth_limit, compare);
+            arr = right;
+        } else {
+            quicksort_3way_within(right, depth_limit, compare);
+            arr = left;
+        }
+    }
+}
+
+/// Measure input characteristics.
+fn measure<T, F>(arr: &[T], integer: bool, compare: &mut F) -> Characteristics
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = arr.len();
+    let descents = arr
+        .windows(2)
+        .filter(|w| compare(&w[0], &w[1]) == Ordering::Greater)
+        .count();
+
+    // Sample at evenly spaced positions.
+    let sample_len = len.min(SAMPLE);
+    let mut sample: Vec<&T> = (0..sample_len)
+        .map(|i| &arr[i * len / sample_len])
+        .collect();
+    let sample_inversions = inversions_by(&sample, |a, b| compare(a, b));
+    insertion_sort_by(&mut sample, |a, b| compare(a, b));
+    let sample_distinct = if sample.is_empty() {
+        0
+    } else {
+        1 + sample
+            .windows(2)
+            .filter(|w| compare(w[0], w[1]) != Ordering::Equal)
+            .count()
+    };
+
+    Characteristics {
+        len,
+        descents,
+        sample_len,
+        sample_inversions,
+        sample_distinct,
+        integer,
+    }
+}
+
+/// Choose an algorithm by `policy`.
+fn choose<T, F>(arr: &[T], policy: SortPolicy, integer: bool, compare: &mut F) -> Explanation
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let characteristics = measure(arr, integer, compare);
+    let explain = |algorithm, reason: String| Explanation {
+        algorithm,
+        reason,
+        characteristics,
+    };
+    let c = characteristics;
+    let log_len = (usize::BITS - c.len.leading_zeros()) as usize;
+
+    let (stable, in_place) = match policy {
+        SortPolicy::Force(Algorithm::RadixSort) if !integer => {
+            let mut explanation = choose(arr, SortPolicy::Auto, integer, compare);
+            explanation.reason = format!(
+                "radix sort requested but elements are not integers, fell back to auto: {}",
+                explanation.reason
+            );
+            return explanation;
+        }
+        SortPolicy::Force(algorithm) => return explain(algorithm, "forced by policy".into()),
+        SortPolicy::Auto => (false, false),
+        SortPolicy::Stable => (true, false),
+        SortPolicy::InPlace => (false, true),
+    };
+
+    if c.len <= SMALL {
+        return explain(
+            Algorithm::InsertionSort,
+            format!("{} elements, small enough for insertion sort", c.len),
+        );
+    }
+    if c.descents == 0 {
+        return explain(
+            Algorithm::InsertionSort,
+            "already sorted, insertion sort takes one pass".into(),
+        );
+    }
+    if c.descents <= log_len && c.sample_inversions == 0 {
+        return explain(
+            Algorithm::InsertionSort,
+            format!(
+                "nearly sorted with {} descents and no inversion in {} samples",
+                c.descents, c.sample_len
+            ),
+        );
+    }
+    if integer && !in_place && c.len >= RADIX_MIN {
+        return explain(
+            Algorithm::RadixSort,
+            format!("{} integers, radix sort takes linear time", c.len),
+        );
+    }
+    if !stable && c.sample_distinct * 4 <= c.sample_len {
+        return explain(
+            Algorithm::Quicksort3Way,
+            format!(
+                "many duplicates, {} distinct values in {} samples",
+                c.sample_distinct, c.sample_len
+            ),
+        );
+    }
+    if in_place {
+        return explain(
+            Algorithm::Heapsort,
+            "no dominant pattern, heapsort needs no buffer".into(),
+        );
+    }
+    explain(
+        Algorithm::Mergesort,
+        "no dominant pattern, mergesort is stable and O(n log n)".into(),
+    )
+}
+
+/// Type IDs of primitive integers.
+fn is_integer<T: 'static>() -> bool {
+    let id = TypeId::of::<T>();
+    [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<isize>(),
+    ]
+    .contains(&id)
+}
+
+/// Radix sort if `T` is a primitive integer. Returns whether sorted.
+fn radix_sort_integers<T: 'static>(arr: &mut [T]) -> bool {
+    macro_rules! dispatch {
+        ($($ty:ty),+) => {
+            $(if TypeId::of::<T>() == TypeId::of::<$ty>() {
+                // Safety: `T` is `$ty`, checked by type IDs.
+                let arr = unsafe { &mut *(arr as *mut [T] as *mut [$ty]) };
+                radix_sort(arr);
+                return true;
+            })+
+        };
+    }
+    dispatch!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+    false
+}
+
+#[cfg(test)]
+mod base {
+    use super::*;
+
+    fn sort_stable<T: Ord + 'static>(arr: &mut [T]) {
+        sort_with(arr, SortPolicy::Stable);
+    }
+
+    base_cases!(sort);
+    stability_cases!(sort_stable);
+    by_cases!(sort_by, sort_by_key);
+}
+
+#[cfg(test)]
+mod dispatch {
+    use super::*;
+    use crate::sorting::{Pattern, SortStats};
+
+    fn chosen<T: Ord + Clone + 'static>(arr: &[T], policy: SortPolicy) -> Algorithm {
+        let mut sorted = arr.to_vec();
+        let explanation = sort_with(&mut sorted, policy);
+        assert!(sorted.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(explanation, explain_sort(arr, policy));
+        explanation.algorithm
+    }
+
+    fn strings(arr: &[i32]) -> Vec<String> {
+        arr.iter().map(|v| format!("{:08}", v)).collect()
+    }
+
+    #[test]
+    fn choices() {
+        use Algorithm::*;
+        let random = Pattern::Random.generate(1000, 0);
+        let few_unique = Pattern::FewUnique(4).generate(1000, 0);
+        let sorted = Pattern::Sorted.generate(1000, 0);
+        let nearly_sorted = Pattern::NearlySorted(2).generate(1000, 0);
+
+        assert_eq!(chosen(&[3, 1, 2], SortPolicy::Auto), InsertionSort);
+        assert_eq!(chosen(&sorted, SortPolicy::Auto), InsertionSort);
+        assert_eq!(chosen(&nearly_sorted, SortPolicy::Auto), InsertionSort);
+        assert_eq!(chosen(&random, SortPolicy::Auto), RadixSort);
+        assert_eq!(chosen(&few_unique, SortPolicy::Auto), RadixSort);
+        assert_eq!(
+            chosen(&strings(&few_unique), SortPolicy::Auto),
+            Quicksort3Way
+        );
+        assert_eq!(chosen(&strings(&random), SortPolicy::Auto), Mergesort);
+
+        assert_eq!(chosen(&strings(&few_unique), SortPolicy::Stable), Mergesort);
+        assert_eq!(chosen(&random, SortPolicy::InPlace), Heapsort);
+        assert_eq!(chosen(&few_unique, SortPolicy::InPlace), Quicksort3Way);
+    }
+
+    #[test]
+    fn inversions_between_samples() {
+        // Two adjacent sorted blocks swapped between two sample points give
+        // one descent and no sampled inversion, but Θ(n^2) inversions.
+        let len = 1 << 17;
+        let mut arr: Vec<u32> = (0..len).collect();
+        let gap = len as usize / SAMPLE;
+        arr[gap + 1..2 * gap].rotate_left(gap / 2);
+        let explanation = explain_sort(&arr, SortPolicy::Auto);
+        assert_eq!(explanation.algorithm, Algorithm::InsertionSort);
+        assert_eq!(explanation.characteristics.descents, 1);
+
+        let mut sorted = arr.clone();
+        let stats = SortStats::collect(|| {
+            let explanation = sort_with(&mut sorted, SortPolicy::Auto);
+            assert_eq!(explanation.algorithm, Algorithm::Mergesort);
+            assert!(explanation.reason.contains("fell back to mergesort"));
+        });
+        assert!(sorted.windows(2).all(|w| w[0] <= w[1]));
+        assert!(stats.swaps <= INSERTION_SWAPS_PER_ELEMENT * len as usize + SMALL * SMALL / 2);
+
+        let mut sorted = arr;
+        let explanation = sort_with(&mut sorted, SortPolicy::InPlace);
+        assert_eq!(explanation.algorithm, Algorithm::Heapsort);
+        assert!(sorted.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn duplicates_only_at_sample_positions() {
+        // Every sampled element is equal, all others are distinct, so 3-way
+        // quicksort is chosen for input with hardly any duplicates.
+        for &len in &[20_000, 80_000] {
+            let mut arr: Vec<i32> = (0..len as i32).collect();
+            for i in 0..SAMPLE {
+                arr[i * len / SAMPLE] = -1;
+            }
+            let mut res = arr.clone();
+            res.sort();
+            let stats = SortStats::collect(|| {
+                let explanation = sort_with_by(&mut arr, SortPolicy::Auto, i32::cmp);
+                assert_eq!(explanation.algorithm, Algorithm::Quicksort3Way);
+            });
+            assert_eq!(arr, res);
+            let n_log_n = len * (usize::BITS - len.leading_zeros()) as usize;
+            assert!(stats.comparisons < 5 * n_log_n, "{} {:?}", len, stats);
+        }
+    }
+
+    #[test]
+    fn policies_respected() {
+        for pattern in Pattern::ALL.iter() {
+            let arr = strings(&pattern.generate(2000, 1));
+            assert!(chosen(&arr, SortPolicy::Stable).is_stable());
+            assert!(chosen(&arr, SortPolicy::InPlace).is_in_place());
+            let arr = pattern.generate(2000, 1);
+            assert!(chosen(&arr, SortPolicy::InPlace).is_in_place());
+        }
+    }
+
+    #[test]
+    fn forced() {
+        let arr = Pattern::Random.generate(1000, 0);
+        let policy = SortPolicy::Force(Algorithm::Heapsort);
+        assert_eq!(chosen(&arr, policy), Algorithm::Heapsort);
+
+        // Radix sort falls back for non-integers.
+        let policy = SortPolicy::Force(Algorithm::RadixSort);
+        let explanation = explain_sort(&strings(&arr), policy);
+        assert_eq!(explanation.algorithm, Algorithm::Mergesort);
+        assert!(explanation.reason.starts_with("radix sort requested"));
+        assert_eq!(chosen(&arr, policy), Algorithm::RadixSort);
+    }
+
+    #[test]
+    fn explanation() {
+        let arr: Vec<u64> = (0..1000).rev().collect();
+        let explanation = explain_sort(&arr, SortPolicy::Auto);
+        assert_eq!(
+            explanation.to_string(),
+            "radix_sort: 1000 integers, radix sort takes linear time"
+        );
+        let c = explanation.characteristics;
+        assert_eq!((c.len, c.descents, c.sample_len), (1000, 999, 64));
+        assert_eq!((c.sample_inversions, c.sample_distinct), (64 * 63 / 2, 64));
+        assert!(c.integer);
+    }
+
+    #[test]
+    fn every_pattern() {
+        for pattern in Pattern::ALL.iter() {
+            for &len in &[0, 1, 33, 1000, 20_000] {
+                let arr = pattern.generate(len, 2);
+                let mut res = arr.clone();
+                res.sort();
+                for policy in [SortPolicy::Auto, SortPolicy::Stable, SortPolicy::InPlace].iter() {
+                    let mut ints = arr.clone();
+                    sort_with(&mut ints, *policy);
+                    assert_eq!(ints, res);
+                    let mut by = arr.clone();
+                    sort_with_by(&mut by, *policy, |a, b| b.cmp(a));
+                    by.reverse();
+                    assert_eq!(by, res);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use std::cmp::Ordering;
+
+use crate::sorting::stats::{self, swap};
+use crate::sorting::{insertion_sort_by, trace};
+
+/// Runs of this size are sorted by insertion sort before merging.
+const RUN: usize = 16;
+
+/// Block mergesort.
+///
+/// A stable sort in O(n log n) time with O(1) extra memory, after WikiSort
+/// and GrailSort. Nothing is allocated; the buffer for merging is taken
+/// from the input itself.
+///
+/// 1. Up to about 2√n distinct elements are collected at the front as keys.
+///    Each key is the first of its value, so it is put back before the equal
+///    elements at the end.
+/// 2. The rest is sorted by insertion sort in runs of `RUN` elements, which
+///    are then merged bottom-up, two runs at a time:
+///    * Both runs are cut into blocks of about √(2 · run) elements. Each
+///      block is tagged with a key, and blocks are sorted by their first
+///      element, ties broken by the tags so the left run goes first.
+///    * Neighbouring blocks from different runs are merged. Keys are
+///      distinct, so their order does not matter, and a block of keys is
+///      used as the buffer for merging by swapping.
+/// 3. If there are too few distinct elements for a buffer, blocks are
+///    larger and merged by rotations instead. With that few distinct
+///    values, the rotations still take linear time per level.
+/// 4. Keys are sorted and merged back by rotations.
+///
+/// References:
+///
+/// - [Block sort](https://en.wikipedia.org/wiki/Block_sort)
+/// - Kim and Kutzner, "Ratio Based Stable In-Place Merging"
+pub fn block_mergesort<T: Ord>(arr: &mut [T]) {
+    block_mergesort_by(arr, T::cmp);
+}
+
+/// Block mergesort with a comparator function.
+// ANCHOR: block_mergesort
+pub fn block_mergesort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let len = arr.len();
+    if len <= RUN {
+        insertion_sort_by(arr, &mut compare);
+        return;
+    }
+
+    // 1. Collect keys. One key means every element is equal.
+    let mut run = RUN;
+    while run * 2 < len {
+        run *= 2;
+    }
+    let block = block_len(run);
+    let keys = collect_keys(arr, block + 2 * run / block, &mut compare);
+    if keys == 1 {
+        return;
+    }
+
+    // 2. Sort runs, and merge them bottom-up.
+    for chunk in arr[keys..].chunks_mut(RUN) {
+        insertion_sort_by(chunk, &mut compare);
+    }
+    let mut run = RUN;
+    while run < len - keys {
+        merge_level(arr, keys, run, &mut compare);
+        run *= 2;
+    }
+
+    // 3. Put keys back.
+    insertion_sort_by(&mut arr[..keys], &mut compare);
+    rotation_merge(arr, keys, true, &mut compare);
+}
+// ANCHOR_END: block_mergesort
+
+/// Block mergesort with a key extraction function.
+pub fn block_mergesort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    block_mergesort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Block length for merging runs of `run` elements with a buffer, the
+/// smallest power of two not less than √(2 · run). It balances the number
+/// of tags and the size of the buffer.
+fn block_len(run: usize) -> usize {
+    let mut block = 1;
+    while block * block < 2 * run {
+        block *= 2;
+    }
+    block
+}
+
+/// Whether `x` goes before `y`, where `x` is from the left run if `left`.
+fn before<T, F>(x: &T, y: &T, left: bool, compare: &mut F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    match compare(x, y) {
+        Ordering::Less => true,
+        Ordering::Equal => left,
+        Ordering::Greater => false,
+    }
+}
+
+/// Move up to `wanted` distinct elements to the front of `arr` in ascending
+/// order, and return how many are found. The first element of each value is
+/// taken; others keep their order.
+fn collect_keys<T, F>(arr: &mut [T], wanted: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Keys are kept at `arr[first..first + keys]`, and moved along only when
+    // a new key is found, so each other element is passed once.
+    let (mut first, mut keys) = (0, 1);
+    for i in 1..arr.len() {
+        if keys == wanted {
+            break;
+        }
+        let pos =
+            arr[first..first + keys].partition_point(|k| compare(k, &arr[i]) == Ordering::Less);
+        if pos < keys && compare(&arr[i], &arr[first + pos]) == Ordering::Equal {
+            continue;
+        }
+        if first + keys < i {
+            rotate_left(&mut arr[first..i], keys);
+            first = i - keys;
+        }
+        rotate_left(&mut arr[first + pos..=i], keys - pos);
+        keys += 1;
+    }
+    rotate_left(&mut arr[..first + keys], first);
+    keys
+}
+
+/// Merge every two neighbouring runs of `run` elements of `arr[keys..]`.
+/// `arr[..keys]` are distinct keys, used as tags and buffer.
+// ANCHOR: merge_level
+fn merge_level<T, F>(arr: &mut [T], keys: usize, run: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = arr.len();
+    // Runs short enough are merged as a single block.
+    let (block, buffered) = if keys >= run + 2 {
+        (run, true)
+    } else if keys >= block_len(run) + 2 * run / block_len(run) {
+        (block_len(run), true)
+    } else {
+        let mut block = 1;
+        while 2 * run / block > keys {
+            block *= 2;
+        }
+        (block, false)
+    };
+    insertion_sort_by(&mut arr[..2 * run / block], &mut *compare);
+
+    // The buffer sits right before the runs being merged, and moves to the
+    // right after them. It is moved back when the level is done.
+    let mut start = keys;
+    while start < len {
+        let mid = (start + run).min(len);
+        let end = (start + 2 * run).min(len);
+        if mid < end && compare(&arr[mid], &arr[mid - 1]) == Ordering::Less {
+            if buffered {
+                merge_blocks_buffered(arr, start - block, run, end - mid, block, compare);
+            } else {
+                merge_blocks_in_place(arr, start, run, end - mid, block, compare);
+            }
+        } else if buffered {
+            for i in start..end {
+                swap(arr, i - block, i);
+            }
+        }
+        start = end;
+    }
+    if buffered {
+        for i in (keys - block..len - block).rev() {
+            swap(arr, i, i + block);
+        }
+    }
+}
+// ANCHOR_END: merge_level
+
+/// Sort blocks of `arr[start..]`, `left` blocks from the left run followed
+/// by `right` blocks from the right run, by their first elements. Tags
+/// `arr[..left + right]` are sorted along with blocks. Return the index of
+/// the tag of the first right block, which tells where a block is from.
+fn sort_blocks<T, F>(
+    arr: &mut [T],
+    start: usize,
+    left: usize,
+    right: usize,
+    block: usize,
+    compare: &mut F,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let count = left + right;
+    let mut mid = left;
+    for i in 0..count {
+        let mut min = i;
+        for j in i + 1..count {
+            let ord = compare(&arr[start + j * block], &arr[start + min * block]);
+            if ord == Ordering::Less
+                || ord == Ordering::Equal && compare(&arr[j], &arr[min]) == Ordering::Less
+            {
+                min = j;
+            }
+        }
+        if min != i {
+            swap_ranges(arr, start + i * block, start + min * block, block);
+            swap(arr, i, min);
+            if mid == i {
+                mid = min;
+            } else if mid == min {
+                mid = i;
+            }
+        }
+    }
+    mid
+}
+
+/// Whether the block tagged `arr[tag]` is from the left run.
+fn is_left<T, F>(arr: &[T], tag: usize, mid: usize, count: usize, compare: &mut F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    mid == count || compare(&arr[tag], &arr[mid]) == Ordering::Less
+}
+
+/// Merge `arr[start + block..][..left_len]` and the following `right_len`
+/// elements, with the buffer `arr[start..start + block]`, which is moved to
+/// the end.
+// ANCHOR: merge_blocks_buffered
+fn merge_blocks_buffered<T, F>(
+    arr: &mut [T],
+    start: usize,
+    left_len: usize,
+    right_len: usize,
+    block: usize,
+    compare: &mut F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let (left, right) = (left_len / block, right_len / block);
+    let count = left + right;
+    let mid = sort_blocks(arr, start + block, left, right, block, compare);
+
+    // The pending part of the previous blocks, `arr[out + block..][..pending]`,
+    // is from the left run if `from_left`. Everything before the buffer is
+    // in place.
+    let mut out = start;
+    let mut pending = block;
+    let mut from_left = is_left(arr, 0, mid, count, compare);
+    for i in 1..count {
+        let next_left = is_left(arr, i, mid, count, compare);
+        if next_left == from_left {
+            swap_ranges(arr, out, out + block, pending);
+            out += pending;
+            pending = block;
+            continue;
+        }
+        // Merge by swapping into the buffer until either side runs out.
+        let (mut l, l_end) = (out + block, out + block + pending);
+        let (mut r, r_end) = (l_end, l_end + block);
+        while l < l_end && r < r_end {
+            if before(&arr[r], &arr[l], !from_left, compare) {
+                swap(arr, out, r);
+                r += 1;
+            } else {
+                swap(arr, out, l);
+                l += 1;
+            }
+            out += 1;
+        }
+        if l < l_end {
+            // The rest of the pending part goes after the buffer.
+            pending = l_end - l;
+            swap_ranges(arr, l, r_end - pending, pending);
+        } else {
+            pending = r_end - r;
+            from_left = next_left;
+        }
+    }
+    swap_ranges(arr, out, out + block, pending);
+    out += pending;
+
+    insertion_sort_by(&mut arr[..count], &mut *compare);
+
+    // Merge the last partial block from the right, over the buffer.
+    let (merged, tail) = (out, right_len % block);
+    let (mut l, mut r, mut out) = (merged, merged + block + tail, merged + tail);
+    while r > merged + block {
+        if l > start && compare(&arr[r - 1], &arr[l - 1]) == Ordering::Less {
+            swap(arr, out - 1, l - 1);
+            l -= 1;
+        } else {
+            swap(arr, out - 1, r - 1);
+            r -= 1;
+        }
+        out -= 1;
+    }
+}
+// ANCHOR_END: merge_blocks_buffered
+
+/// Merge `arr[start..][..left_len]` and the following `right_len` elements
+/// without a buffer.
+fn merge_blocks_in_place<T, F>(
+    arr: &mut [T],
+    start: usize,
+    left_len: usize,
+    right_len: usize,
+    block: usize,
+    compare: &mut F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let (left, right) = (left_len / block, right_len / block);
+    let count = left + right;
+    let mid = sort_blocks(arr, start, left, right, block, compare);
+
+    // The pending part of the previous blocks is `arr[first..][..pending]`.
+    let mut first = start;
+    let mut pending = block;
+    let mut from_left = is_left(arr, 0, mid, count, compare);
+    for i in 1..count {
+        let next_left = is_left(arr, i, mid, count, compare);
+        let next = first + pending;
+        if next_left == from_left {
+            first = next;
+            pending = block;
+            continue;
+        }
+        // Merge up to where either side runs out.
+        let (last, next_last) = (next - 1, next + block - 1);
+        if before(&arr[last], &arr[next_last], from_left, compare) {
+            let n = arr[next..next + block]
+                .partition_point(|x| before(x, &arr[last], !from_left, compare));
+            rotation_merge(&mut arr[first..next + n], pending, from_left, compare);
+            first = next + n;
+            pending = block - n;
+            from_left = next_left;
+        } else {
+            let n = arr[first..next]
+                .partition_point(|x| before(x, &arr[next_last], from_left, compare));
+            rotate_left(&mut arr[first + n..next + block], pending - n);
+            rotation_merge(&mut arr[first..first + n + block], n, from_left, compare);
+            first += n + block;
+            pending -= n;
+        }
+    }
+
+    insertion_sort_by(&mut arr[..count], &mut *compare);
+
+    // Merge the last partial block from the right.
+    let end = start + left_len + right_len;
+    rotation_merge(
+        &mut arr[start..end],
+        end - right_len % block - start,
+        true,
+        compare,
+    );
+}
+
+/// Merge `arr[..mid]` and `arr[mid..]` in place by rotations. Equal elements
+/// of the left side go first if `left`.
+///
+/// Elements of the longer side are moved once, and elements of the shorter
+/// side once per rotation, which is at most the number of distinct values.
+// ANCHOR: rotation_merge
+fn rotation_merge<T, F>(arr: &mut [T], mid: usize, left: bool, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = arr.len();
+    if mid == 0 || mid == len {
+        return;
+    }
+    if mid <= len - mid {
+        // Move groups of the left side to the right, from the smallest.
+        let (mut l, mut r) = (0, mid);
+        loop {
+            l += arr[l..r].partition_point(|x| before(x, &arr[r], left, compare));
+            if l == r {
+                return;
+            }
+            let n = arr[r..].partition_point(|y| !before(&arr[l], y, left, compare));
+            rotate_left(&mut arr[l..r + n], r - l);
+            l += n;
+            r += n;
+            if r == len {
+                return;
+            }
+        }
+    } else {
+        // Move groups of the right side to the left, from the largest.
+        let (mut l, mut r) = (mid, len);
+        loop {
+            r = l + arr[l..r].partition_point(|y| !before(&arr[l - 1], y, left, compare));
+            if l == r {
+                return;
+            }
+            let n = arr[..l].partition_point(|x| before(x, &arr[r - 1], left, compare));
+            rotate_left(&mut arr[n..r], l - n);
+            r -= l - n;
+            l = n;
+            if l == 0 {
+                return;
+            }
+        }
+    }
+}
+// ANCHOR_END: rotation_merge
+
+/// Swap `arr[a..a + len]` and `arr[b..b + len]`, which do not overlap.
+fn swap_ranges<T>(arr: &mut [T], a: usize, b: usize, len: usize) {
+    for i in 0..len {
+        swap(arr, a + i, b + i);
+    }
+}
+
+/// Rotate in place, and record it.
+fn rotate_left<T>(arr: &mut [T], mid: usize) {
+    arr.rotate_left(mid);
+    stats::record_moves(arr.len());
+    trace::writes(arr);
+}
+
+#[cfg(test)]
+mod base {
+    use super::*;
+    base_cases!(block_mergesort);
+    stability_cases!(block_mergesort);
+    by_cases!(block_mergesort_by, block_mergesort_by_key);
+}
+
+#[cfg(test)]
+mod in_place {
+    use super::*;
+    use crate::sorting::test_cases::allocations;
+    use crate::sorting::SortStats;
+
+    #[test]
+    fn random() {
+        for &len in &[0, 1, 16, 17, 19, 20, 21, 39, 41, 100, 1000, 4097] {
+            for &values in &[1, 2, 3, 10, 50, 1000, i32::MAX] {
+                let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % values).collect();
+                let mut res = arr.clone();
+                res.sort();
+                block_mergesort(&mut arr);
+                assert_eq!(arr, res, "values = {}", values);
+            }
+        }
+    }
+
+    #[test]
+    fn stable_with_many_duplicates() {
+        for &values in &[2, 10, 40, 100, 1000] {
+            let arr: Vec<_> = (0..5000)
+                .map(|i| (rand::random::<i32>() % values, i))
+                .collect();
+            let mut res = arr.clone();
+            res.sort();
+            let mut arr = arr;
+            block_mergesort_by_key(&mut arr, |t| t.0);
+            assert_eq!(arr, res, "values = {}", values);
+        }
+    }
+
+    #[test]
+    fn n_log_n() {
+        // Rotation based merges like SymMerge take O(n log² n) swaps and moves.
+        let len: usize = 1 << 14;
+        for &values in &[2, 16, 64, 256, 1 << 12, len as i32] {
+            let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % values).collect();
+            let stats = SortStats::collect(|| block_mergesort(&mut arr));
+            assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+            let n_log_n = 14 * len;
+            assert!(
+                stats.comparisons < 3 * n_log_n,
+                "values = {}: {:?}",
+                values,
+                stats
+            );
+            assert!(
+                stats.swaps + stats.moves < 4 * n_log_n,
+                "values = {}: {:?}",
+                values,
+                stats
+            );
+        }
+    }
+
+    #[test]
+    fn never_allocates() {
+        for &len in &[10, 1000, 10_000] {
+            let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i64>()).collect();
+            let mut stats = SortStats::default();
+            let count = allocations(|| stats = SortStats::collect(|| block_mergesort(&mut arr)));
+            assert_eq!(count, 0, "len = {}", len);
+            assert_eq!(stats.allocations, 0);
+            assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+        }
+
+        // The counter does see allocations of other sorts.
+        let mut arr = [3, 1, 2];
+        assert!(allocations(|| crate::sorting::mergesort(&mut arr)) > 0);
+    }
+}
@@ -1,47 +1,97 @@
+use std::cmp::Ordering;
+
+use crate::sorting::stats::{self, swap};
+
 /// Bubble sort
-pub fn bubble_sort(arr: &mut [i32]) {
-    let mut swapped = true;
+pub fn bubble_sort<T: Ord>(arr: &mut [T]) {
+    bubble_sort_by(arr, T::cmp);
+}
+
+/// Bubble sort with a comparator function.
+// ANCHOR: bubble_sort
+pub fn bubble_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let mut swapped = true; // 1
     while swapped {
         // No swap means array is sorted.
         swapped = false;
         for i in 1..arr.len() {
-            if arr[i - 1] > arr[i] {
-                arr.swap(i - 1, i);
-                swapped = true
+            // 2
+            if compare(&arr[i - 1], &arr[i]) == Ordering::Greater {
+                swap(arr, i - 1, i);
+                swapped = true // 3
             }
         }
     }
 }
+// ANCHOR_END: bubble_sort
+
+/// Bubble sort with a key extraction function.
+pub fn bubble_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    bubble_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
 
 /// Optimized bubble sort
 ///
 /// Memorize last swapped index to avoid unnecessary check.
-pub fn bubble_sort_optimized(arr: &mut [i32]) {
+pub fn bubble_sort_optimized<T: Ord>(arr: &mut [T]) {
+    bubble_sort_optimized_by(arr, T::cmp);
+}
+
+/// Optimized bubble sort with a comparator function.
+// ANCHOR: bubble_sort_optimized
+pub fn bubble_sort_optimized_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let mut new_len: usize;
-    let mut len = arr.len();
+    let mut len = arr.len(); // 1
     loop {
         new_len = 0;
         for i in 1..len {
-            if arr[i - 1] > arr[i] {
-                arr.swap(i - 1, i);
-                new_len = i;
+            if compare(&arr[i - 1], &arr[i]) == Ordering::Greater {
+                swap(arr, i - 1, i);
+                new_len = i; // 2
             }
         }
         if new_len == 0 {
+            // 3
             break;
         }
-        len = new_len;
+        len = new_len; // 4
     }
 }
+// ANCHOR_END: bubble_sort_optimized
+
+/// Optimized bubble sort with a key extraction function.
+pub fn bubble_sort_optimized_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    bubble_sort_optimized_by(arr, |a, b| key(a).cmp(&key(b)));
+}
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(bubble_sort);
+    stability_cases!(bubble_sort);
+    by_cases!(bubble_sort_by, bubble_sort_by_key);
 }
 
 #[cfg(test)]
 mod optimized {
     use super::*;
     base_cases!(bubble_sort_optimized);
+    stability_cases!(bubble_sort_optimized);
+    by_cases!(bubble_sort_optimized_by, bubble_sort_optimized_by_key);
 }
@@ -1,3 +1,7 @@
+use std::cmp::Ordering;
+
+use crate::sorting::{insertion_sort_by, stats, trace};
+
 /// Bucket sort
 ///
 /// * `arr` - Collection of value to be sorted in place.
@@ -20,11 +24,15 @@ where
 
         // 2.2 Search if the bucket with same hash exists.
         let value = value.clone();
+        stats::record_moves(1);
         match buckets.binary_search_by(|bucket| bucket.hash.cmp(&hash)) {
             // If exists, push the value to the bucket.
             Ok(index) => buckets[index].values.push(value),
             // If none, create and new bucket and insert value in.
-            Err(index) => buckets.insert(index, Bucket::new(hash, value)),
+            Err(index) => {
+                stats::record_allocation();
+                buckets.insert(index, Bucket::new(hash, value))
+            }
         }
     }
 
@@ -38,7 +46,10 @@ where
         .collect::<Vec<T>>();
 
     // 4. Clone back to original array.
+    stats::record_allocation();
+    stats::record_moves(ret.len());
     arr.clone_from_slice(&ret);
+    trace::writes(arr);
 }
 
 /// Bucket to store elements.
@@ -60,13 +71,106 @@ impl<H, T> Bucket<H, T> {
     }
 }
 
+/// Floating-point numbers which can be sorted by `bucket_sort_float`.
+pub trait BucketFloat: Copy {
+    /// Total order of IEEE 754 numbers, like `f64::total_cmp`.
+    fn total_cmp(&self, other: &Self) -> Ordering;
+
+    /// Convert to `f64` losslessly.
+    fn to_f64(self) -> f64;
+}
+
+impl BucketFloat for f32 {
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.into()
+    }
+}
+
+impl BucketFloat for f64 {
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Bucket sort for floating-point numbers.
+///
+/// Numbers are sorted in total order, the same as `f64::total_cmp`:
+/// negative NaN, negative infinity, negative numbers, `-0.0`, `0.0`,
+/// positive numbers, positive infinity, then positive NaN.
+///
+/// `n` buckets are created for `n` numbers, evenly dividing the range
+/// `[min, max)` of finite numbers. Numbers before or after all finite numbers
+/// go to the first or the last bucket. Each bucket is sorted by insertion
+/// sort. Runs in O(n) on average if numbers are uniformly distributed.
+///
+/// * `arr` - Collection of value to be sorted in place.
+pub fn bucket_sort_float<T: BucketFloat>(arr: &mut [T]) {
+    let len = arr.len();
+    if len < 2 {
+        return;
+    }
+
+    // 1. Find the range of finite numbers.
+    let finite = arr.iter().map(|v| v.to_f64()).filter(|v| v.is_finite());
+    let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+
+    // 2. Map numbers to buckets. Halved to avoid overflowing on wide ranges.
+    // Rounding errors never break the order of buckets.
+    let width = max / 2.0 - min / 2.0;
+    let bucket_of = |v: T| {
+        let v = v.to_f64();
+        if v.is_nan() {
+            if v.is_sign_negative() {
+                0
+            } else {
+                len - 1
+            }
+        } else if v <= min || width <= 0.0 {
+            // Also the case that there is no finite number at all.
+            0
+        } else if v >= max {
+            len - 1
+        } else {
+            (((v / 2.0 - min / 2.0) / width * len as f64) as usize).min(len - 1)
+        }
+    };
+
+    // 3. Distribute numbers into buckets.
+    let mut buckets: Vec<Vec<T>> = vec![Vec::new(); len];
+    stats::record_allocation();
+    for &v in arr.iter() {
+        buckets[bucket_of(v)].push(v);
+    }
+    stats::record_moves(len);
+
+    // 4. Sort each bucket and copy back to original array.
+    let mut start = 0;
+    for mut bucket in buckets {
+        insertion_sort_by(&mut bucket, T::total_cmp);
+        arr[start..start + bucket.len()].copy_from_slice(&bucket);
+        start += bucket.len();
+    }
+    stats::record_moves(len);
+    trace::writes(arr);
+}
+
 #[cfg(test)]
 mod base {
     use super::*;
     fn bucket_sort_(arr: &mut [i32]) {
         bucket_sort(arr, |int| int / 4);
     }
-    base_cases!(bucket_sort_);
+    base_cases!(bucket_sort_, i32);
 }
 
 #[cfg(test)]
@@ -75,5 +179,76 @@ mod stability {
     fn bucket_sort_(arr: &mut [(i32, i32)]) {
         bucket_sort(arr, |t| t.0 / 4);
     }
-    stability_cases!(bucket_sort_);
+    stability_cases!(bucket_sort_, (i32, i32));
+}
+
+#[cfg(test)]
+mod float {
+    use super::*;
+
+    /// Compare bits, since NaN never equals to itself.
+    fn assert_sorted(mut arr: Vec<f64>) {
+        let mut res = arr.clone();
+        res.sort_by(f64::total_cmp);
+        bucket_sort_float(&mut arr);
+        let bits = |arr: &[f64]| arr.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&arr), bits(&res));
+    }
+
+    #[test]
+    fn empty_and_one() {
+        assert_sorted(vec![]);
+        assert_sorted(vec![1.0]);
+    }
+
+    #[test]
+    fn uniform() {
+        assert_sorted((0..10000).map(|_| rand::random::<f64>()).collect());
+    }
+
+    #[test]
+    fn clustered() {
+        let mut arr: Vec<_> = (0..1000).map(|_| rand::random::<f64>() * 1e-9).collect();
+        arr.extend((0..10).map(|_| rand::random::<f64>() * 1e9));
+        assert_sorted(arr);
+    }
+
+    #[test]
+    fn all_equal() {
+        assert_sorted(vec![3.5; 100]);
+    }
+
+    #[test]
+    fn nan() {
+        let neg_nan = -f64::NAN;
+        assert_sorted(vec![f64::NAN, 1.0, neg_nan, -1.0, f64::NAN, 0.5]);
+        assert_sorted(vec![f64::NAN, neg_nan, f64::NAN]);
+    }
+
+    #[test]
+    fn infinities() {
+        assert_sorted(vec![f64::INFINITY, 1.0, f64::NEG_INFINITY, -2.0, 3.0]);
+        assert_sorted(vec![f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY]);
+        assert_sorted(vec![f64::MAX, -f64::MAX, 0.0, f64::MIN_POSITIVE, 1.0]);
+    }
+
+    #[test]
+    fn negative_zero() {
+        let mut arr: Vec<f64> = vec![0.0, -0.0, 0.0, -0.0, -1.0];
+        bucket_sort_float(&mut arr);
+        assert!(arr[1].is_sign_negative() && arr[2].is_sign_negative());
+        assert!(arr[3].is_sign_positive() && arr[4].is_sign_positive());
+        assert_sorted(vec![0.0, -0.0, 1.0, -0.0, 0.0]);
+    }
+
+    #[test]
+    fn f32() {
+        let mut arr: Vec<f32> = (0..1000).map(|_| rand::random::<f32>() - 0.5).collect();
+        arr.extend_from_slice(&[f32::NAN, -0.0, f32::NEG_INFINITY, f32::MAX]);
+        let mut res = arr.clone();
+        res.sort_by(f32::total_cmp);
+        bucket_sort_float(&mut arr);
+        let bits = |arr: &[f32]| arr.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&arr), bits(&res));
+    }
 }
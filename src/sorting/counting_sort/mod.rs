@@ -1,48 +1,179 @@
-/// Counting sort
+use std::ops::RangeInclusive;
+
+use crate::sorting::radix_sort::RadixKey;
+use crate::sorting::{stats, trace};
+
+/// Keys may span at most this many values, 2^24, so that no more than
+/// 128 MiB of counters are allocated.
+pub(crate) const MAX_KEY_RANGE: u64 = 1 << 24;
+
+/// Counting sort.
+///
+/// A stable sort by integer keys. The key range is inferred from elements,
+/// and `max - min + 1` counters are allocated.
+///
+/// Elements are moved to their final positions by swapping, so `T` is not
+/// required to be `Clone`.
+///
+/// * `arr` - Collection of value to be sorted in place.
+/// * `key` - Function extracting an integer key from elements.
+///
+/// # Panics
+///
+/// Panics if keys span more than 2^24 values.
+pub fn counting_sort<T, K, F>(arr: &mut [T], key: F)
+where
+    K: RadixKey,
+    F: Fn(&T) -> K,
+{
+    let (keys, min, max) = extract_keys(arr, key);
+    let mut dests = destinations(&keys, min, max);
+    move_to_destinations(arr, &mut dests);
+}
+
+/// Counting sort with a known inclusive key range.
 ///
 /// * `arr` - Collection of value to be sorted in place.
-/// * `min` - Lower bound of the integer range.
-/// * `max` - Upper bound of the integer range.
-/// * `key` - Function extracting key witn the integer range from elements.
-pub fn counting_sort<F, T>(arr: &mut [T], min: usize, max: usize, key: F)
+/// * `range` - Inclusive range of keys, e.g. `0..=9` for decimal digits.
+/// * `key` - Function extracting a key within `range` from elements.
+///
+/// # Panics
+///
+/// Panics if `range` is empty or spans more than 2^24 values, or if a key is
+/// out of `range`.
+pub fn counting_sort_in_range<T, K, F>(arr: &mut [T], range: RangeInclusive<K>, key: F)
+where
+    K: RadixKey,
+    F: Fn(&T) -> K,
+{
+    let (min, max) = (range.start().radix_key(), range.end().radix_key());
+    assert!(min <= max, "key range must not be empty");
+    range_len(min, max);
+    let (keys, _, _) = extract_keys(arr, key);
+    assert!(
+        keys.iter().all(|k| (min..=max).contains(k)),
+        "key out of range"
+    );
+    let mut dests = destinations(&keys, min, max);
+    move_to_destinations(arr, &mut dests);
+}
+
+/// Counting sort from `src` into a caller provided buffer `dst`.
+///
+/// Elements are cloned into `dst`, and `src` is left untouched.
+///
+/// # Panics
+///
+/// Panics if `src` and `dst` have different lengths, or if keys span more
+/// than 2^24 values.
+pub fn counting_sort_into<T, K, F>(src: &[T], dst: &mut [T], key: F)
 where
-    F: Fn(&T) -> usize,
     T: Clone,
+    K: RadixKey,
+    F: Fn(&T) -> K,
 {
-    let mut prefix_sums = {
-        // 1. Initialize the count array with default value 0.
-        let len = max - min;
-        let mut count_arr = Vec::with_capacity(len);
-        count_arr.resize(len, 0);
-
-        // 2. Scan elements to collect counts.
-        for value in arr.iter() {
-            count_arr[key(value)] += 1;
-        }
+    assert_eq!(
+        src.len(),
+        dst.len(),
+        "src and dst must have the same length"
+    );
+    let (keys, min, max) = extract_keys(src, key);
+    let dests = destinations(&keys, min, max);
+    for (value, &dest) in src.iter().zip(&dests) {
+        dst[dest] = value.clone();
+    }
+    stats::record_moves(src.len());
+    trace::writes(dst);
+}
 
-        // 3. Calculate prefix sum.
-        count_arr
-            .into_iter()
-            .scan(0, |state, x| {
-                *state += x;
-                Some(*state - x)
-            })
-            .collect::<Vec<usize>>()
-    };
+/// Extract keys of all elements, and find the minimum and maximum in the
+/// same pass.
+fn extract_keys<T, K, F>(arr: &[T], key: F) -> (Vec<u64>, u64, u64)
+where
+    K: RadixKey,
+    F: Fn(&T) -> K,
+{
+    let mut min = u64::MAX;
+    let mut max = 0;
+    let keys = arr
+        .iter()
+        .map(|value| {
+            let k = key(value).radix_key();
+            min = min.min(k);
+            max = max.max(k);
+            k
+        })
+        .collect();
+    stats::record_allocation();
+    (keys, min, max)
+}
+
+/// Find the final position of each element, given their keys in range
+/// `min..=max`.
+fn destinations(keys: &[u64], min: u64, max: u64) -> Vec<usize> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    // 1. Initialize the count array with default value 0.
+    let mut prefix_sums = vec![0; range_len(min, max)];
+    stats::record_allocation();
 
-    // 4. Use prefix sum as index position of output element.
-    for value in arr.to_vec().iter() {
-        let index = key(value);
-        arr[prefix_sums[index]] = value.clone();
-        prefix_sums[index] += 1;
+    // 2. Scan keys to collect counts.
+    for &k in keys {
+        prefix_sums[(k - min) as usize] += 1;
+    }
+
+    // 3. Calculate prefix sum, the start position of each key.
+    let mut sum = 0;
+    for count in prefix_sums.iter_mut() {
+        sum += *count;
+        *count = sum - *count;
+    }
+
+    // 4. Use prefix sum as position of each element. Elements with equal
+    // keys are placed in their original order.
+    stats::record_allocation();
+    keys.iter()
+        .map(|&k| {
+            let index = (k - min) as usize;
+            prefix_sums[index] += 1;
+            prefix_sums[index] - 1
+        })
+        .collect()
+}
+
+/// Number of keys in `min..=max`.
+///
+/// # Panics
+///
+/// Panics if it exceeds `MAX_KEY_RANGE`.
+fn range_len(min: u64, max: u64) -> usize {
+    let len = max.checked_sub(min).and_then(|d| d.checked_add(1));
+    match len {
+        Some(len) if len <= MAX_KEY_RANGE => len as usize,
+        _ => panic!("key range must not exceed 2^24 values"),
+    }
+}
+
+/// Move elements to their final positions by following cycles of the
+/// permutation. Each swap puts at least one element in place.
+fn move_to_destinations<T>(arr: &mut [T], dests: &mut [usize]) {
+    for i in 0..arr.len() {
+        while dests[i] != i {
+            let j = dests[i];
+            stats::swap(arr, i, j);
+            dests.swap(i, j);
+        }
     }
 }
 
 #[cfg(test)]
 mod base {
     use super::*;
-    fn counting_sort_(arr: &mut [i32]) {
-        counting_sort(arr, 1, 10, |int| *int as usize);
+    use crate::sorting::test_cases::Case;
+
+    fn counting_sort_<T: Case<i32>>(arr: &mut [T]) {
+        counting_sort(arr, |v| v.to_case());
     }
     base_cases!(counting_sort_);
 }
@@ -50,8 +181,107 @@ mod base {
 #[cfg(test)]
 mod stability {
     use super::*;
-    fn counting_sort_(arr: &mut [(i32, i32)]) {
-        counting_sort(arr, 1, 10, |t| t.0 as usize);
+    use crate::sorting::test_cases::Case;
+
+    fn counting_sort_<T: Case<(i32, i32)>>(arr: &mut [T]) {
+        counting_sort(arr, |t| t.to_case().0);
     }
     stability_cases!(counting_sort_);
 }
+
+#[cfg(test)]
+mod range {
+    use super::*;
+
+    #[test]
+    fn inclusive() {
+        let mut arr = [10, 1, 5, 10, 1];
+        counting_sort_in_range(&mut arr, 1..=10, |v| *v);
+        assert_eq!(arr, [1, 1, 5, 10, 10]);
+    }
+
+    #[test]
+    fn signed_keys() {
+        let mut arr: Vec<i8> = (0..1000).map(|_| rand::random()).collect();
+        arr.extend_from_slice(&[i8::MIN, i8::MAX]);
+        let mut res = arr.clone();
+        res.sort();
+
+        let mut inferred = arr.clone();
+        counting_sort(&mut inferred, |v| *v);
+        assert_eq!(inferred, res);
+
+        counting_sort_in_range(&mut arr, i8::MIN..=i8::MAX, |v| *v);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn narrow_range_of_wide_keys() {
+        let mut arr = [i64::MAX, i64::MAX - 2, i64::MAX - 1];
+        counting_sort(&mut arr, |v| *v);
+        assert_eq!(arr, [i64::MAX - 2, i64::MAX - 1, i64::MAX]);
+    }
+
+    #[test]
+    #[should_panic(expected = "key out of range")]
+    fn out_of_range() {
+        counting_sort_in_range(&mut [1, 2, 11], 1..=10, |v| *v);
+    }
+
+    #[test]
+    fn widest_range() {
+        let max = MAX_KEY_RANGE as u32 - 1;
+        let mut arr = [max, 0, max, 1];
+        counting_sort(&mut arr, |v| *v);
+        assert_eq!(arr, [0, 1, max, max]);
+    }
+
+    #[test]
+    #[should_panic(expected = "key range must not exceed 2^24 values")]
+    fn full_range() {
+        counting_sort(&mut [i64::MIN, i64::MAX], |v| *v);
+    }
+
+    #[test]
+    #[should_panic(expected = "key range must not exceed 2^24 values")]
+    fn too_wide_range() {
+        counting_sort(&mut [0, MAX_KEY_RANGE], |v| *v);
+    }
+
+    #[test]
+    #[should_panic(expected = "key range must not exceed 2^24 values")]
+    fn too_wide_given_range() {
+        counting_sort_in_range(&mut [0], 0..=u64::MAX, |v| *v);
+    }
+
+    #[test]
+    #[should_panic(expected = "key range must not be empty")]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn reversed_range() {
+        counting_sort_in_range(&mut [1, 2], 10..=1, |v| *v);
+    }
+}
+
+#[cfg(test)]
+mod into {
+    use super::*;
+
+    #[test]
+    fn reuse_buffer() {
+        let mut buf = vec![(0, ' '); 6];
+        let src = [(2, 'a'), (-1, 'b'), (2, 'c'), (0, 'd'), (-1, 'e'), (2, 'f')];
+        counting_sort_into(&src, &mut buf, |t| t.0);
+        let values: String = buf.iter().map(|t| t.1).collect();
+        assert_eq!(values, "bedacf");
+
+        // The same buffer for another input.
+        counting_sort_into(&[(3, 'g'); 6], &mut buf, |t| t.0);
+        assert_eq!(buf, [(3, 'g'); 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn length_mismatch() {
+        counting_sort_into(&[1, 2], &mut [0], |v: &i32| *v);
+    }
+}
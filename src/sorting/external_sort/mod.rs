@@ -0,0 +1,467 @@
+use std::cmp::Ordering;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{self, AtomicUsize};
+
+use crate::sorting::heapsort::{heapify, sift_down};
+use crate::sorting::mergesort_by;
+
+/// Maximum number of runs merged at once, to limit open files.
+const MAX_FAN_IN: usize = 64;
+
+/// Smallest read buffer worth giving each run while merging.
+const MIN_RUN_BUFFER: usize = 512;
+
+/// Read buffer of the input, the same as `BufReader`'s default.
+const INPUT_BUFFER: usize = 8 * 1024;
+
+/// Format of records read and written by external sort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Records terminated by `\n`. The terminator of the last record is
+    /// optional on input, and always written on output.
+    Lines,
+    /// Binary records of a fixed number of bytes.
+    FixedWidth(usize),
+}
+
+/// External merge sort.
+///
+/// Sort records in byte order, for data larger than memory.
+///
+/// 1. Read records from `input` into chunks of about `budget` bytes. A chunk
+///    counts its records, their offsets, and the buffer to sort offsets.
+/// 2. Sort each chunk with mergesort and spill it to a temporary file.
+/// 3. K-way merge sorted runs into `output`, with ties broken by run order
+///    to keep the sort stable. The budget is shared by read buffers of runs
+///    merged at once, so a small budget merges fewer runs at a time, in
+///    more passes.
+///
+/// The budget does not cover the head record of each run being merged, nor
+/// the buffers of the input and the output.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{external_sort, RecordFormat};
+///
+/// let input = "pear\napple\nfig\n";
+/// let mut output = Vec::new();
+/// external_sort(input.as_bytes(), &mut output, RecordFormat::Lines, 8).unwrap();
+/// assert_eq!(output, b"apple\nfig\npear\n");
+/// ```
+pub fn external_sort<R, W>(
+    input: R,
+    output: W,
+    format: RecordFormat,
+    budget: usize,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    external_sort_by(input, output, format, budget, |a, b| a.cmp(b))
+}
+
+/// External merge sort with a comparator function.
+// ANCHOR: external_sort
+pub fn external_sort_by<R, W, F>(
+    input: R,
+    output: W,
+    format: RecordFormat,
+    budget: usize,
+    mut compare: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&[u8], &[u8]) -> Ordering,
+{
+    let mut reader = RecordReader::new(input, format)?;
+    let mut output = BufWriter::new(output);
+
+    // 1. Sort chunks and spill them as runs.
+    let mut runs = Vec::new();
+    loop {
+        let (mut chunk, done) = reader.read_chunk(budget)?;
+        let data = &chunk.data;
+        mergesort_by(&mut chunk.records, |&(a, a_end), &(b, b_end)| {
+            compare(&data[a..a_end], &data[b..b_end])
+        });
+        if done && runs.is_empty() {
+            // Everything fits in memory.
+            for record in chunk.iter() {
+                write_record(&mut output, record, format)?;
+            }
+            return output.flush();
+        }
+        if !chunk.records.is_empty() {
+            let run = TempFile::new()?;
+            let mut writer = BufWriter::new(File::create(&run.path)?);
+            for record in chunk.iter() {
+                write_record(&mut writer, record, format)?;
+            }
+            writer.flush()?;
+            runs.push(run);
+        }
+        if done {
+            break;
+        }
+    }
+
+    // 2. Merge groups of runs until few enough to merge at once.
+    let (fan_in, run_buffer) = merge_plan(budget);
+    while runs.len() > fan_in {
+        let mut merged = Vec::new();
+        for group in runs.chunks(fan_in) {
+            let run = TempFile::new()?;
+            let writer = BufWriter::new(File::create(&run.path)?);
+            merge_runs(group, writer, format, run_buffer, &mut compare)?;
+            merged.push(run);
+        }
+        runs = merged;
+    }
+
+    // 3. Merge into output.
+    merge_runs(&runs, output, format, run_buffer, &mut compare)
+}
+// ANCHOR_END: external_sort
+
+/// External merge sort with a key extraction function.
+pub fn external_sort_by_key<R, W, K, F>(
+    input: R,
+    output: W,
+    format: RecordFormat,
+    budget: usize,
+    mut key: F,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+    K: Ord,
+    F: FnMut(&[u8]) -> K,
+{
+    external_sort_by(input, output, format, budget, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Number of runs merged at once, and the read buffer size of each run, so
+/// that read buffers fit in `budget`.
+// ANCHOR: merge_plan
+fn merge_plan(budget: usize) -> (usize, usize) {
+    let fan_in = (budget / MIN_RUN_BUFFER).clamp(2, MAX_FAN_IN);
+    (fan_in, (budget / fan_in).max(1))
+}
+// ANCHOR_END: merge_plan
+
+/// K-way merge sorted runs into `output`, reading each run through a buffer
+/// of `capacity` bytes.
+///
+/// Heads of runs are kept in a binary heap, so each record is output in
+/// O(log k) comparisons.
+// ANCHOR: merge_runs
+fn merge_runs<W, F>(
+    runs: &[TempFile],
+    mut output: W,
+    format: RecordFormat,
+    capacity: usize,
+    compare: &mut F,
+) -> io::Result<()>
+where
+    W: Write,
+    F: FnMut(&[u8], &[u8]) -> Ordering,
+{
+    let mut readers = runs
+        .iter()
+        .map(|run| RecordReader::with_capacity(capacity, File::open(&run.path)?, format))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // Heap of head records and their run indices. Comparison is reversed to
+    // keep the least record at the root of heapsort's max-heap.
+    let mut heap = Vec::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = reader.read_record()? {
+            heap.push((record, i));
+        }
+    }
+    let mut heap_compare =
+        |a: &(Vec<u8>, usize), b: &(Vec<u8>, usize)| compare(&b.0, &a.0).then(b.1.cmp(&a.1));
+    heapify(&mut heap, &mut heap_compare);
+
+    while !heap.is_empty() {
+        write_record(&mut output, &heap[0].0, format)?;
+        // Replace the root with the next record of the same run.
+        match readers[heap[0].1].read_record()? {
+            Some(record) => heap[0].0 = record,
+            None => {
+                heap.swap_remove(0);
+            }
+        }
+        sift_down(&mut heap, 0, &mut heap_compare);
+    }
+    output.flush()
+}
+// ANCHOR_END: merge_runs
+
+/// Records in memory, stored back to back in a single buffer.
+// ANCHOR: chunk
+struct Chunk {
+    data: Vec<u8>,
+    /// Start and end offsets of records in `data`.
+    records: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    /// Bytes allocated for the chunk, and for mergesort to sort its offsets.
+    fn size(&self) -> usize {
+        self.data.capacity() + 2 * self.records.capacity() * mem::size_of::<(usize, usize)>()
+    }
+
+    /// Records in the order of offsets.
+    fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.records
+            .iter()
+            .map(move |&(start, end)| &self.data[start..end])
+    }
+}
+// ANCHOR_END: chunk
+
+/// Reader splitting input into records.
+struct RecordReader<R> {
+    inner: BufReader<R>,
+    format: RecordFormat,
+}
+
+impl<R: Read> RecordReader<R> {
+    fn new(inner: R, format: RecordFormat) -> io::Result<Self> {
+        Self::with_capacity(INPUT_BUFFER, inner, format)
+    }
+
+    fn with_capacity(capacity: usize, inner: R, format: RecordFormat) -> io::Result<Self> {
+        if format == RecordFormat::FixedWidth(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record width must be positive",
+            ));
+        }
+        Ok(RecordReader {
+            inner: BufReader::with_capacity(capacity, inner),
+            format,
+        })
+    }
+
+    /// Read next record. `None` if no more records.
+    fn read_record(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut record = Vec::new();
+        Ok(self.append_record(&mut record)?.map(|_| record))
+    }
+
+    /// Append next record to `buf`, and return where it starts. `None` if no
+    /// more records.
+    fn append_record(&mut self, buf: &mut Vec<u8>) -> io::Result<Option<usize>> {
+        let start = buf.len();
+        match self.format {
+            RecordFormat::Lines => {
+                if self.inner.read_until(b'\n', buf)? == 0 {
+                    return Ok(None);
+                }
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                }
+                Ok(Some(start))
+            }
+            RecordFormat::FixedWidth(width) => {
+                buf.reserve(width);
+                let read = (&mut self.inner).take(width as u64).read_to_end(buf)?;
+                match read {
+                    0 => Ok(None),
+                    n if n == width => Ok(Some(start)),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "incomplete fixed-width record",
+                    )),
+                }
+            }
+        }
+    }
+
+    /// Read records of about `budget` bytes, at least one record.
+    ///
+    /// Return the records, and whether the input is exhausted.
+    fn read_chunk(&mut self, budget: usize) -> io::Result<(Chunk, bool)> {
+        let mut chunk = Chunk {
+            data: Vec::new(),
+            records: Vec::new(),
+        };
+        while chunk.records.is_empty() || chunk.size() < budget {
+            match self.append_record(&mut chunk.data)? {
+                Some(start) => chunk.records.push((start, chunk.data.len())),
+                None => return Ok((chunk, true)),
+            }
+        }
+        Ok((chunk, false))
+    }
+}
+
+/// Write a record in `format`.
+fn write_record<W: Write>(output: &mut W, record: &[u8], format: RecordFormat) -> io::Result<()> {
+    output.write_all(record)?;
+    if format == RecordFormat::Lines {
+        output.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// A uniquely named temporary file, removed when dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn new() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let n = COUNTER.fetch_add(1, atomic::Ordering::Relaxed);
+            let name = format!("rust-algorithm-club-{}-{}.run", process::id(), n);
+            let path = env::temp_dir().join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(TempFile { path }),
+                // Left by a previous process with the same id.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort_lines(input: &str, budget: usize) -> String {
+        let mut output = Vec::new();
+        external_sort(input.as_bytes(), &mut output, RecordFormat::Lines, budget).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(sort_lines("", 16), "");
+    }
+
+    #[test]
+    fn in_memory() {
+        assert_eq!(sort_lines("b\nc\na\n", 1024), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn missing_last_newline() {
+        assert_eq!(sort_lines("b\nc\na", 1), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn lines_with_tiny_budget() {
+        // About 10 records per run, merged two at a time in many passes.
+        let lines: Vec<_> = (0..2000)
+            .map(|_| rand::random::<u32>().to_string())
+            .collect();
+        let mut res = lines.clone();
+        res.sort();
+        let input = lines.join("\n");
+        let output = sort_lines(&input, 64);
+        assert_eq!(output, res.join("\n") + "\n");
+    }
+
+    #[test]
+    fn fixed_width_with_tiny_budget() {
+        let values: Vec<u32> = (0..1000).map(|_| rand::random()).collect();
+        let input: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        let mut output = Vec::new();
+        external_sort(&input[..], &mut output, RecordFormat::FixedWidth(4), 40).unwrap();
+
+        let mut res = values;
+        res.sort();
+        let output: Vec<_> = output
+            .chunks(4)
+            .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        assert_eq!(output, res);
+    }
+
+    #[test]
+    fn merge_within_budget() {
+        assert_eq!(merge_plan(40), (2, 20));
+        assert_eq!(merge_plan(4096), (8, 512));
+        assert_eq!(merge_plan(1 << 20), (MAX_FAN_IN, (1 << 20) / MAX_FAN_IN));
+        for &budget in &[0, 1, 40, 1000, 4096, 1 << 20] {
+            let (fan_in, capacity) = merge_plan(budget);
+            assert!(fan_in >= 2);
+            assert!(fan_in * capacity <= budget.max(fan_in));
+        }
+    }
+
+    #[test]
+    fn budget_counts_overhead() {
+        // Empty records take no bytes, but their offsets do.
+        let input = "\n".repeat(1000);
+        let mut reader = RecordReader::new(input.as_bytes(), RecordFormat::Lines).unwrap();
+        let (chunk, done) = reader.read_chunk(1024).unwrap();
+        assert!(!done);
+        assert!(chunk.records.len() < 100);
+        assert!(chunk.iter().all(|r| r.is_empty()));
+        assert_eq!(sort_lines(&input, 1024), input);
+    }
+
+    #[test]
+    fn stable() {
+        // Sort by the first byte only, the rest tells the input order.
+        let lines: Vec<_> = (0..2000)
+            .map(|i| format!("{}{:04}", rand::random::<u8>() % 4, i))
+            .collect();
+        let mut res = lines.clone();
+        res.sort_by_key(|s| s.as_bytes()[0]);
+        let mut output = Vec::new();
+        external_sort_by_key(
+            lines.join("\n").as_bytes(),
+            &mut output,
+            RecordFormat::Lines,
+            32,
+            |r| r[0],
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), res.join("\n") + "\n");
+    }
+
+    #[test]
+    fn descending() {
+        let mut output = Vec::new();
+        external_sort_by(
+            &b"a\nc\nb\n"[..],
+            &mut output,
+            RecordFormat::Lines,
+            1,
+            |a, b| b.cmp(a),
+        )
+        .unwrap();
+        assert_eq!(output, b"c\nb\na\n");
+    }
+
+    #[test]
+    fn incomplete_record() {
+        let mut output = Vec::new();
+        let err = external_sort(&[1, 2, 3][..], &mut output, RecordFormat::FixedWidth(2), 16)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err =
+            external_sort(&[1, 2][..], &mut output, RecordFormat::FixedWidth(0), 16).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
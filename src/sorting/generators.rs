@@ -0,0 +1,565 @@
+//! Input generators for testing and benchmarking sorting algorithms.
+//!
+//! Inputs are built from a seed with a small deterministic pseudo-random
+//! number generator, so a failing case can be reproduced anywhere.
+
+use std::cmp::Ordering;
+
+/// SplitMix64, a tiny pseudo-random number generator.
+///
+/// Not suitable for cryptography, but fast, and the same seed always gives
+/// the same sequence.
+///
+/// Reference: Steele, Lea and Flood, "Fast Splittable Pseudorandom Number
+/// Generators".
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Create a generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Next pseudo-random 64-bit integer.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Next pseudo-random integer in `0..n`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "empty range");
+        // Multiply-shift instead of modulo, which is nearly unbiased for any
+        // `n` much less than 2^64.
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// Shapes of input data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// Uniformly random values in `-n..n`.
+    Random,
+    /// `0, 1, ..., n - 1`.
+    Sorted,
+    /// `n - 1, n - 2, ..., 0`.
+    Reversed,
+    /// Random values among the given number of distinct values.
+    FewUnique(usize),
+    /// Every value is the same.
+    AllEqual,
+    /// Ascending to the middle, then descending: `0, 1, 2, 2, 1, 0`.
+    OrganPipe,
+    /// Ascending runs of the given period: `0, 1, 2, 0, 1, 2, 0`.
+    Sawtooth(usize),
+    /// Sorted, then the given number of random pairs swapped.
+    NearlySorted(usize),
+}
+
+impl Pattern {
+    /// Every pattern, with some typical parameters.
+    pub const ALL: [Pattern; 8] = [
+        Pattern::Random,
+        Pattern::Sorted,
+        Pattern::Reversed,
+        Pattern::FewUnique(4),
+        Pattern::AllEqual,
+        Pattern::OrganPipe,
+        Pattern::Sawtooth(64),
+        Pattern::NearlySorted(10),
+    ];
+
+    /// Generate `len` values of this pattern from `seed`.
+    ///
+    /// ```
+    /// use rust_algorithm_club::sorting::Pattern;
+    ///
+    /// assert_eq!(Pattern::OrganPipe.generate(6, 0), [0, 1, 2, 2, 1, 0]);
+    /// assert_eq!(Pattern::Random.generate(100, 42), Pattern::Random.generate(100, 42));
+    /// ```
+    pub fn generate(self, len: usize, seed: u64) -> Vec<i32> {
+        let mut rng = SplitMix64::new(seed);
+        let n = len as i64;
+        match self {
+            Pattern::Random => (0..len)
+                .map(|_| (rng.below(2 * len as u64) as i64 - n) as i32)
+                .collect(),
+            Pattern::Sorted => (0..n).map(|i| i as i32).collect(),
+            Pattern::Reversed => (0..n).rev().map(|i| i as i32).collect(),
+            Pattern::FewUnique(k) => (0..len).map(|_| rng.below(k as u64) as i32).collect(),
+            Pattern::AllEqual => vec![42; len],
+            Pattern::OrganPipe => (0..n).map(|i| i.min(n - 1 - i) as i32).collect(),
+            Pattern::Sawtooth(period) => (0..len).map(|i| (i % period) as i32).collect(),
+            Pattern::NearlySorted(swaps) => {
+                let mut arr = Pattern::Sorted.generate(len, seed);
+                if len > 0 {
+                    for _ in 0..swaps {
+                        let i = rng.below(len as u64) as usize;
+                        let j = rng.below(len as u64) as usize;
+                        arr.swap(i, j);
+                    }
+                }
+                arr
+            }
+        }
+    }
+}
+
+/// McIlroy's adversary against quicksort.
+///
+/// Run `sort` on `len` placeholder elements with a comparator that decides
+/// values lazily. All elements start as "gas", greater than any decided
+/// value. When two gas elements are compared, the one likely to be a pivot
+/// is frozen to the next smallest value, so every partition is as
+/// unbalanced as possible. The values decided are returned as the input.
+///
+/// Sorting the returned input by the same deterministic algorithm takes
+/// quadratic time for any quicksort which picks pivots in a fixed way.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{antiqsort, quicksort_by, SortStats};
+///
+/// let mut arr = antiqsort(100, |arr, compare| quicksort_by(arr, compare));
+/// let stats = SortStats::collect(|| quicksort_by(&mut arr, i32::cmp));
+/// assert!(stats.comparisons >= 100 * 100 / 4);
+/// ```
+///
+/// Reference: M. D. McIlroy, "A Killer Adversary for Quicksort".
+pub fn antiqsort<F>(len: usize, sort: F) -> Vec<i32>
+where
+    F: FnOnce(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering),
+{
+    let gas = len as i32;
+    let mut values = vec![gas; len];
+    let mut solid = 0;
+    let mut candidate = 0;
+    let mut arr: Vec<_> = (0..len).collect();
+    let mut compare = |&x: &usize, &y: &usize| {
+        if values[x] == gas && values[y] == gas {
+            let frozen = if x == candidate { x } else { y };
+            values[frozen] = solid;
+            solid += 1;
+        }
+        if values[x] == gas {
+            candidate = x;
+        } else if values[y] == gas {
+            candidate = y;
+        }
+        values[x].cmp(&values[y])
+    };
+    sort(&mut arr, &mut compare);
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::*;
+
+    #[test]
+    fn deterministic() {
+        for pattern in Pattern::ALL.iter() {
+            assert_eq!(pattern.generate(1000, 7), pattern.generate(1000, 7));
+        }
+        assert_ne!(
+            Pattern::Random.generate(1000, 7),
+            Pattern::Random.generate(1000, 8)
+        );
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(Pattern::Sorted.generate(4, 0), [0, 1, 2, 3]);
+        assert_eq!(Pattern::Reversed.generate(4, 0), [3, 2, 1, 0]);
+        assert_eq!(Pattern::AllEqual.generate(3, 0), [42, 42, 42]);
+        assert_eq!(Pattern::OrganPipe.generate(5, 0), [0, 1, 2, 1, 0]);
+        assert_eq!(Pattern::Sawtooth(3).generate(7, 0), [0, 1, 2, 0, 1, 2, 0]);
+        assert!(Pattern::FewUnique(3)
+            .generate(100, 0)
+            .iter()
+            .all(|v| (0..3).contains(v)));
+        assert!(Pattern::Random
+            .generate(100, 0)
+            .iter()
+            .all(|v| (-100..100).contains(v)));
+
+        let arr = Pattern::NearlySorted(5).generate(1000, 0);
+        let misplaced = arr
+            .iter()
+            .enumerate()
+            .filter(|(i, v)| *i as i32 != **v)
+            .count();
+        assert!(misplaced <= 10);
+        for pattern in Pattern::ALL.iter() {
+            assert!(pattern.generate(0, 0).is_empty());
+        }
+    }
+
+    #[test]
+    fn below_is_in_range() {
+        let mut rng = SplitMix64::new(0);
+        let mut seen = [false; 10];
+        for _ in 0..1000 {
+            seen[rng.below(10) as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+
+    #[test]
+    fn antiqsort_is_quadratic() {
+        let n = 2000;
+        let quadratic = n * n / 4;
+
+        type SortBy = fn(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering);
+        type Sort = fn(&mut [i32]);
+        let victims: [(&str, SortBy, Sort); 3] = [
+            ("quicksort", |arr, cmp| quicksort_by(arr, cmp), quicksort),
+            (
+                "quicksort_hoare",
+                |arr, cmp| quicksort_hoare_by(arr, cmp),
+                quicksort_hoare,
+            ),
+            (
+                "quicksort_3way",
+                |arr, cmp| quicksort_3way_by(arr, cmp),
+                quicksort_3way,
+            ),
+        ];
+        for (name, sort_by, sort) in victims.iter() {
+            let mut arr = antiqsort(n, sort_by);
+            let stats = SortStats::collect(|| sort(&mut arr));
+            assert!(stats.comparisons >= quadratic, "{} {:?}", name, stats);
+        }
+
+        // Introsort and pdqsort bound the damage to O(n log n).
+        let n = 100_000;
+        let n_log_n = n * 17;
+        let mut arr = antiqsort(n, |arr, cmp| pdqsort_by(arr, cmp));
+        let stats = SortStats::collect(|| pdqsort(&mut arr));
+        assert!(stats.comparisons < 5 * n_log_n, "pdqsort {:?}", stats);
+        let mut arr = antiqsort(n, |arr, cmp| introsort_by(arr, cmp));
+        let stats = SortStats::collect(|| introsort(&mut arr));
+        assert!(stats.comparisons < 5 * n_log_n, "introsort {:?}", stats);
+    }
+}
+
+/// Every sort checked against `slice::sort` on every pattern.
+#[cfg(test)]
+mod against_std {
+    use super::*;
+    use crate::sorting::*;
+
+    use std::io::Cursor;
+
+    type Sort = fn(&mut [i32]);
+    type SortBy = fn(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering);
+
+    /// Sizes for sorts O(n log n) or close.
+    const SIZES: [usize; 8] = [0, 1, 2, 3, 10, 100, 1000, 100_000];
+
+    /// Quadratic sorts are checked up to this size.
+    const QUADRATIC_MAX: usize = 2000;
+
+    const CONFIG: ParallelConfig = ParallelConfig {
+        workers: 4,
+        cutoff: 1000,
+    };
+
+    /// Sort integers as 4-byte big-endian keys, whose byte order is the
+    /// same as numeric order.
+    fn by_bytes(arr: &mut [i32], sort: fn(&mut [[u8; 4]])) {
+        let mut keys: Vec<_> = arr
+            .iter()
+            .map(|v| (v.radix_key() as u32).to_be_bytes())
+            .collect();
+        sort(&mut keys);
+        for (v, k) in arr.iter_mut().zip(&keys) {
+            *v = (u32::from_be_bytes(*k) ^ 1 << 31) as i32;
+        }
+    }
+
+    fn external(arr: &mut [i32]) {
+        let input: Vec<u8> = arr
+            .iter()
+            .flat_map(|v| (v.radix_key() as u32).to_be_bytes())
+            .collect();
+        let mut output = Vec::new();
+        external_sort(
+            Cursor::new(input),
+            &mut output,
+            RecordFormat::FixedWidth(4),
+            4096,
+        )
+        .unwrap();
+        for (v, k) in arr.iter_mut().zip(output.chunks(4)) {
+            *v = (u32::from_be_bytes([k[0], k[1], k[2], k[3]]) ^ 1 << 31) as i32;
+        }
+    }
+
+    /// Pad to a power of two with the maximum value, sort and truncate.
+    fn padded(arr: &mut [i32], sort: fn(&mut [i32])) {
+        let mut padded = arr.to_vec();
+        padded.resize(arr.len().next_power_of_two(), i32::MAX);
+        sort(&mut padded);
+        arr.copy_from_slice(&padded[..arr.len()]);
+    }
+
+    /// Name, sort, whether quadratic in the worst case, and a comparator
+    /// version to build an adversary against.
+    const ALGOS: &[(&str, Sort, bool, Option<SortBy>)] = &[
+        (
+            "bubble_sort",
+            bubble_sort,
+            true,
+            Some(|a, c| bubble_sort_by(a, c)),
+        ),
+        ("bubble_sort_optimized", bubble_sort_optimized, true, None),
+        (
+            "insertion_sort",
+            insertion_sort,
+            true,
+            Some(|a, c| insertion_sort_by(a, c)),
+        ),
+        ("binary_insertion_sort", binary_insertion_sort, true, None),
+        (
+            "selection_sort",
+            selection_sort,
+            true,
+            Some(|a, c| selection_sort_by(a, c)),
+        ),
+        (
+            "cycle_sort",
+            cycle_sort,
+            true,
+            Some(|a, c| cycle_sort_by(a, c)),
+        ),
+        (
+            "shellsort",
+            shellsort,
+            false,
+            Some(|a, c| shellsort_by(a, c)),
+        ),
+        (
+            "shellsort_tokuda",
+            |a| shellsort_with(a, &Tokuda),
+            false,
+            None,
+        ),
+        ("heapsort", heapsort, false, Some(|a, c| heapsort_by(a, c))),
+        (
+            "partial_sort",
+            |a| {
+                // The rest is sorted only if the first half is the smallest.
+                let k = a.len() / 2;
+                partial_sort(a, k);
+                heapsort(&mut a[k..]);
+            },
+            false,
+            None,
+        ),
+        (
+            "mergesort",
+            mergesort,
+            false,
+            Some(|a, c| mergesort_by(a, c)),
+        ),
+        ("mergesort_bottom_up", mergesort_bottom_up, false, None),
+        (
+            "parallel_mergesort",
+            |a| parallel_mergesort(a, CONFIG),
+            false,
+            None,
+        ),
+        (
+            "block_mergesort",
+            block_mergesort,
+            false,
+            Some(|a, c| block_mergesort_by(a, c)),
+        ),
+        (
+            "quicksort",
+            quicksort,
+            true,
+            Some(|a, c| quicksort_by(a, c)),
+        ),
+        (
+            "quicksort_optimized",
+            quicksort_optimized,
+            true,
+            Some(|a, c| quicksort_optimized_by(a, c)),
+        ),
+        (
+            "quicksort_manual_tco",
+            quicksort_manual_tco,
+            true,
+            Some(|a, c| quicksort_manual_tco_by(a, c)),
+        ),
+        (
+            "quicksort_3way",
+            quicksort_3way,
+            true,
+            Some(|a, c| quicksort_3way_by(a, c)),
+        ),
+        (
+            "quicksort_hoare",
+            quicksort_hoare,
+            true,
+            Some(|a, c| quicksort_hoare_by(a, c)),
+        ),
+        (
+            "parallel_quicksort",
+            |a| parallel_quicksort(a, CONFIG),
+            true,
+            None,
+        ),
+        ("timsort", timsort, false, Some(|a, c| timsort_by(a, c))),
+        (
+            "introsort",
+            introsort,
+            false,
+            Some(|a, c| introsort_by(a, c)),
+        ),
+        ("pdqsort", pdqsort, false, Some(|a, c| pdqsort_by(a, c))),
+        ("sort", sort, false, None),
+        (
+            "sort_by",
+            |a| sort_by(a, i32::cmp),
+            false,
+            Some(|a, c| sort_by(a, c)),
+        ),
+        ("bitonic_sort", |a| padded(a, bitonic_sort), false, None),
+        (
+            "odd_even_mergesort",
+            odd_even_mergesort,
+            false,
+            Some(|a, c| odd_even_mergesort_by(a, c)),
+        ),
+        ("bucket_sort", |a| bucket_sort(a, |v| v >> 8), true, None),
+        (
+            "bucket_sort_float",
+            |a| {
+                let mut floats: Vec<_> = a.iter().map(|&v| v as f64).collect();
+                bucket_sort_float(&mut floats);
+                for (v, f) in a.iter_mut().zip(&floats) {
+                    *v = *f as i32;
+                }
+            },
+            false,
+            None,
+        ),
+        ("counting_sort", |a| counting_sort(a, |v| *v), false, None),
+        ("radix_sort", radix_sort, false, None),
+        ("radix_sort_16", |a| radix_sort_with(a, 16), false, None),
+        (
+            "msd_radix_sort",
+            |a| by_bytes(a, msd_radix_sort),
+            false,
+            None,
+        ),
+        (
+            "quicksort_3way_string",
+            |a| by_bytes(a, quicksort_3way_string),
+            false,
+            None,
+        ),
+        ("external_sort", external, false, None),
+        (
+            "nth_element",
+            |a| {
+                // Only the middle position is checked.
+                if !a.is_empty() {
+                    let k = a.len() / 2;
+                    nth_element(a, k);
+                    let kth = a[k];
+                    a.sort();
+                    assert_eq!(a[k], kth);
+                }
+            },
+            false,
+            None,
+        ),
+    ];
+
+    fn check(input: &[i32], algo: &(&str, Sort, bool, Option<SortBy>), what: &str) {
+        let mut res = input.to_vec();
+        res.sort();
+        let mut arr = input.to_vec();
+        (algo.1)(&mut arr);
+        assert!(
+            arr == res,
+            "{} on {} of length {}",
+            algo.0,
+            what,
+            input.len()
+        );
+    }
+
+    fn check_pattern(pattern: Pattern) {
+        for algo in ALGOS {
+            for &len in SIZES.iter().filter(|&&len| !algo.2 || len <= QUADRATIC_MAX) {
+                let input = pattern.generate(len, len as u64);
+                check(&input, algo, &format!("{:?}", pattern));
+            }
+        }
+    }
+
+    macro_rules! pattern_cases {
+        ($($name:ident: $pattern:expr),+) => {
+            $(#[test]
+            fn $name() {
+                check_pattern($pattern);
+            })+
+        };
+    }
+
+    pattern_cases!(
+        random: Pattern::Random,
+        sorted: Pattern::Sorted,
+        reversed: Pattern::Reversed,
+        few_unique: Pattern::FewUnique(4),
+        all_equal: Pattern::AllEqual,
+        organ_pipe: Pattern::OrganPipe,
+        sawtooth: Pattern::Sawtooth(64),
+        nearly_sorted: Pattern::NearlySorted(10)
+    );
+
+    #[test]
+    fn antiqsort() {
+        // Each sort against an adversary built for itself if possible, or
+        // for quicksort otherwise.
+        for algo in ALGOS {
+            for &len in SIZES.iter().filter(|&&len| !algo.2 || len <= QUADRATIC_MAX) {
+                let victim = algo.3.unwrap_or(|a, c| quicksort_by(a, c));
+                let len = if algo.3.is_none() {
+                    len.min(QUADRATIC_MAX)
+                } else {
+                    len
+                };
+                let input = super::antiqsort(len, victim);
+                let stats = SortStats::collect(|| check(&input, algo, "antiqsort"));
+                // Sorts not quadratic in the worst case stay within
+                // O(n log n) comparisons against their own adversary.
+                if algo.3.is_some() && !algo.2 {
+                    let n_log_n = len * (usize::BITS - len.leading_zeros()) as usize;
+                    assert!(
+                        stats.comparisons <= 5 * n_log_n,
+                        "{} on antiqsort of length {}: {:?}",
+                        algo.0,
+                        len,
+                        stats
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,49 +1,392 @@
+use std::cmp::Ordering;
+
+use crate::sorting::stats::{self, swap};
+
 /// Heapsort.
-pub fn heapsort(arr: &mut [i32]) {
+pub fn heapsort<T: Ord>(arr: &mut [T]) {
+    heapsort_by(arr, T::cmp);
+}
+
+/// Heapsort with a comparator function.
+// ANCHOR: heapsort
+pub fn heapsort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     // -- Heapify part --
     // This procedure would build a valid max-heap.
     // (or min-heap for sorting descendantly)
-    let end = arr.len();
-    for start in (0..end / 2).rev() {
-        // Skip leaf nodes (end / 2).
-        sift_down(arr, start, end - 1);
-    }
+    heapify(arr, &mut compare);
 
     // -- Sorting part --
     // Iteratively sift down unsorted part (the heap).
-    for end in (1..arr.len()).rev() {
-        arr.swap(end, 0);
-        sift_down(arr, 0, end - 1);
+    sort_heap(arr, &mut compare);
+}
+// ANCHOR_END: heapsort
+
+/// Heapsort with a key extraction function.
+pub fn heapsort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    heapsort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Partial sort.
+///
+/// Sort the smallest `k` elements into `arr[..k]` in O(n log k). The order of
+/// the rest elements is unspecified.
+///
+/// A max-heap of the first `k` elements keeps the smallest `k` seen so far.
+/// Each remaining element less than the root replaces it. Finally the heap
+/// is sorted by heapsort.
+///
+/// `k` greater than the length sorts the whole slice.
+pub fn partial_sort<T: Ord>(arr: &mut [T], k: usize) {
+    partial_sort_by(arr, k, T::cmp);
+}
+
+/// Partial sort with a comparator function.
+pub fn partial_sort_by<T, F>(arr: &mut [T], k: usize, compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let k = k.min(arr.len());
+    if k == 0 {
+        return;
+    }
+
+    // Build a max-heap of the first `k` elements.
+    heapify(&mut arr[..k], &mut compare);
+
+    // Replace the root with any smaller element.
+    for i in k..arr.len() {
+        if compare(&arr[i], &arr[0]) == Ordering::Less {
+            swap(arr, 0, i);
+            sift_down(&mut arr[..k], 0, &mut compare);
+        }
+    }
+
+    sort_heap(&mut arr[..k], &mut compare);
+}
+
+/// Partial sort with a key extraction function.
+pub fn partial_sort_by_key<T, K, F>(arr: &mut [T], k: usize, mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    partial_sort_by(arr, k, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Order of items returned by `top_k`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The smallest items, in ascending order.
+    Ascending,
+    /// The largest items, in descending order.
+    Descending,
+}
+
+/// Consume an iterator and return its top `k` items in `order`.
+///
+/// Only `k` items are kept in a bounded heap, so it takes O(k) space and
+/// O(n log k) time for `n` items.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{top_k, SortOrder};
+///
+/// let top = top_k(vec![3, 1, 4, 1, 5, 9, 2, 6], 3, SortOrder::Descending);
+/// assert_eq!(top, [9, 6, 5]);
+/// ```
+pub fn top_k<I>(iter: I, k: usize, order: SortOrder) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    top_k_by(iter, k, order, I::Item::cmp)
+}
+
+/// Consume an iterator and return its top `k` items in `order` with a
+/// comparator function.
+pub fn top_k_by<I, F>(iter: I, k: usize, order: SortOrder, mut compare: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    let compare = |a: &I::Item, b: &I::Item| match order {
+        SortOrder::Ascending => compare(a, b),
+        SortOrder::Descending => compare(b, a),
+    };
+    let mut compare = stats::counting(compare);
+    if k == 0 {
+        return Vec::new();
+    }
+
+    // A max-heap keeping the top `k` items seen so far.
+    let mut iter = iter.into_iter();
+    let mut heap: Vec<_> = iter.by_ref().take(k).collect();
+    stats::record_allocation();
+    heapify(&mut heap, &mut compare);
+
+    // Replace the root with any item ordered before it.
+    for item in iter {
+        if compare(&item, &heap[0]) == Ordering::Less {
+            heap[0] = item;
+            sift_down(&mut heap, 0, &mut compare);
+        }
+    }
+
+    sort_heap(&mut heap, &mut compare);
+    heap
+}
+
+/// Consume an iterator and return its top `k` items in `order` with a key
+/// extraction function.
+pub fn top_k_by_key<I, K, F>(iter: I, k: usize, order: SortOrder, mut key: F) -> Vec<I::Item>
+where
+    I: IntoIterator,
+    K: Ord,
+    F: FnMut(&I::Item) -> K,
+{
+    top_k_by(iter, k, order, |a, b| key(a).cmp(&key(b)))
+}
+
+// -- Sift primitives --
+// Shared by heapsort and `collections::BinaryHeap`. `heap` is a max-heap in
+// an array, where children of node `i` are `2i + 1` and `2i + 2`, and no
+// child is greater than its parent.
+
+// ANCHOR: heapify
+/// Build a max-heap in O(n) by sifting down every non-leaf node, from the
+/// last one to the root.
+pub(crate) fn heapify<T, F>(heap: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Skip leaf nodes (len / 2).
+    for start in (0..heap.len() / 2).rev() {
+        // 1
+        sift_down(heap, start, compare);
+    }
+}
+
+/// Sort a max-heap in place by repeatedly moving the root to the end of the
+/// unsorted part.
+pub(crate) fn sort_heap<T, F>(heap: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    for end in (1..heap.len()).rev() {
+        // 2
+        swap(heap, end, 0); // 3
+        sift_down(&mut heap[..end], 0, compare); // 4
+    }
+}
+// ANCHOR_END: heapify
+
+/// Move the element at `pos` up until its parent is not less than it.
+///
+/// Precondition: `heap[..pos]` is a heap.
+pub(crate) fn sift_up<T, F>(heap: &mut [T], mut pos: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    while pos > 0 {
+        let parent = (pos - 1) / 2;
+        if compare(&heap[parent], &heap[pos]) != Ordering::Less {
+            break;
+        }
+        swap(heap, parent, pos);
+        pos = parent;
     }
 }
 
-/// Internal function for heap to fix itself to conform to heap definition.
-/// Precondiition: all elements below `start` are in heap order
-/// expect `start` itself.
-fn sift_down(arr: &mut [i32], start: usize, end: usize) {
-    let mut root = start;
+/// Move the element at `pos` down until no child is greater than it.
+///
+/// Precondition: both subtrees of `pos` are heaps.
+// ANCHOR: sift_down
+pub(crate) fn sift_down<T, F>(heap: &mut [T], mut pos: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = heap.len();
     loop {
-        let mut child = root * 2 + 1; // Get the left child
-        if child > end {
+        // 1
+        let mut child = pos * 2 + 1; // Get the left child
+        if child >= len {
             break;
         }
-        if child < end && arr[child] < arr[child + 1] {
+        if child + 1 < len && compare(&heap[child], &heap[child + 1]) == Ordering::Less {
+            // 2
             // Right child exists and is greater.
             child += 1;
         }
 
-        if arr[root] < arr[child] {
+        if compare(&heap[pos], &heap[child]) == Ordering::Less {
+            // 3
             // If child is greater than root, swap'em!
-            arr.swap(root, child);
-            root = child;
+            swap(heap, pos, child);
+            pos = child;
         } else {
             break;
         }
     }
 }
+// ANCHOR_END: sift_down
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(heapsort);
+    by_cases!(heapsort_by, heapsort_by_key);
+}
+
+#[cfg(test)]
+mod sift {
+    use super::*;
+    use crate::collections::BinaryHeap;
+
+    fn is_heap(heap: &[i32]) -> bool {
+        (1..heap.len()).all(|i| heap[(i - 1) / 2] >= heap[i])
+    }
+
+    fn random(len: usize) -> Vec<i32> {
+        (0..len).map(|_| rand::random::<i32>() % 100).collect()
+    }
+
+    #[test]
+    fn heapify_and_sift() {
+        for len in 0..50 {
+            let mut heap = random(len);
+            heapify(&mut heap, &mut i32::cmp);
+            assert!(is_heap(&heap));
+
+            // Push by sifting up.
+            heap.push(rand::random::<i32>() % 100);
+            let last = heap.len() - 1;
+            sift_up(&mut heap, last, &mut i32::cmp);
+            assert!(is_heap(&heap));
+
+            // Replace the root by sifting down.
+            heap[0] = rand::random::<i32>() % 100;
+            sift_down(&mut heap, 0, &mut i32::cmp);
+            assert!(is_heap(&heap));
+        }
+    }
+
+    #[test]
+    fn same_as_binary_heap() {
+        // `BinaryHeap` and heapsort share the primitives.
+        let arr = random(1000);
+        let mut sorted = arr.clone();
+        heapsort(&mut sorted);
+        assert_eq!(BinaryHeap::from(arr).into_sorted_vec(), sorted);
+    }
+}
+
+#[cfg(test)]
+mod partial {
+    use super::*;
+
+    fn assert_partial(arr: &[i32], k: usize) {
+        let mut res = arr.to_vec();
+        res.sort();
+        let mut arr = arr.to_vec();
+        partial_sort(&mut arr, k);
+        let k = k.min(arr.len());
+        assert_eq!(arr[..k], res[..k], "k = {}", k);
+        // The rest elements are still there.
+        arr[k..].sort();
+        assert_eq!(arr[k..], res[k..], "k = {}", k);
+    }
+
+    #[test]
+    fn every_k() {
+        let arr: Vec<_> = (0..100).map(|_| rand::random::<i32>() % 30).collect();
+        for k in 0..=arr.len() + 1 {
+            assert_partial(&arr, k);
+        }
+        assert_partial(&[], 3);
+    }
+
+    #[test]
+    fn patterns() {
+        assert_partial(&(0..1000).collect::<Vec<_>>(), 10);
+        assert_partial(&(0..1000).rev().collect::<Vec<_>>(), 10);
+        assert_partial(&[7; 1000], 10);
+    }
+
+    #[test]
+    fn by() {
+        let mut arr = [3, 1, 4, 1, 5, 9, 2, 6];
+        partial_sort_by(&mut arr, 3, |a, b| b.cmp(a));
+        assert_eq!(arr[..3], [9, 6, 5]);
+
+        let mut arr = ["ccc", "a", "bb", "dddd"];
+        partial_sort_by_key(&mut arr, 2, |s| s.len());
+        assert_eq!(arr[..2], ["a", "bb"]);
+    }
+
+    #[test]
+    fn fewer_comparisons_than_heapsort() {
+        let arr: Vec<_> = (0..10000).map(|_| rand::random::<i32>()).collect();
+        let mut a = arr.clone();
+        let partial = crate::sorting::SortStats::collect(|| partial_sort(&mut a, 10));
+        let mut a = arr;
+        let full = crate::sorting::SortStats::collect(|| heapsort(&mut a));
+        assert!(partial.comparisons * 5 < full.comparisons);
+    }
+}
+
+#[cfg(test)]
+mod top {
+    use super::*;
+
+    #[test]
+    fn ascending_and_descending() {
+        let arr: Vec<_> = (0..1000).map(|_| rand::random::<i32>() % 100).collect();
+        let mut res = arr.clone();
+        res.sort();
+        for &k in &[0, 1, 10, 999, 1000, 2000] {
+            let n = k.min(arr.len());
+            let top = top_k(arr.iter().copied(), k, SortOrder::Ascending);
+            assert_eq!(top, res[..n], "k = {}", k);
+            let top = top_k(arr.iter().copied(), k, SortOrder::Descending);
+            let expected: Vec<_> = res.iter().rev().take(n).copied().collect();
+            assert_eq!(top, expected, "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let top = top_k(
+            (0..1_000_000i64).map(|i| (i * 7919) % 1_000_003),
+            5,
+            SortOrder::Ascending,
+        );
+        assert_eq!(top, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn comparator_and_key() {
+        let words = vec!["pear", "fig", "banana", "kiwi", "apple"];
+        let top = top_k_by(words.clone(), 2, SortOrder::Descending, |a, b| {
+            a.len().cmp(&b.len())
+        });
+        assert_eq!(top, ["banana", "apple"]);
+        let top = top_k_by_key(words, 2, SortOrder::Ascending, |s| s.len());
+        assert_eq!(top[0], "fig");
+        assert_eq!(top[1].len(), 4);
+    }
+
+    #[test]
+    fn non_clone_items() {
+        let items = (0..100).map(|i| Box::new(i % 10));
+        let top = top_k(items, 3, SortOrder::Descending);
+        assert_eq!(top, [Box::new(9), Box::new(9), Box::new(9)]);
+    }
 }
@@ -1,39 +1,105 @@
+use std::cmp::Ordering;
+
+use crate::sorting::stats::{self, swap};
+
+// ANCHOR: insertion_sort
 /// Insertion sort.
-pub fn insertion_sort(arr: &mut [i32]) {
+pub fn insertion_sort<T: Ord>(arr: &mut [T]) {
+    insertion_sort_by(arr, T::cmp);
+}
+
+/// Insertion sort with a comparator function.
+pub fn insertion_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     for i in 1..arr.len() {
+        // 1
         let mut j = i;
-        while j > 0 && arr[j - 1] > arr[j] {
-            arr.swap(j - 1, j);
+        while j > 0 && compare(&arr[j - 1], &arr[j]) == Ordering::Greater {
+            // 2
+            swap(arr, j - 1, j);
             j -= 1;
         }
     }
 }
+// ANCHOR_END: insertion_sort
+
+/// Insertion sort with a key extraction function.
+pub fn insertion_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    insertion_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
 
 /// Binary insertion sort.
 ///
 /// Binary insertion sort is a insertion sort variant that utilizes binary
 /// search to reduce comparisons in a normal insertion sort.
-pub fn binary_insertion_sort(arr: &mut [i32]) {
-    for i in 1..arr.len() {
-        let val = arr[i];
+pub fn binary_insertion_sort<T: Ord>(arr: &mut [T]) {
+    binary_insertion_sort_by(arr, T::cmp);
+}
+
+/// Binary insertion sort with a comparator function.
+pub fn binary_insertion_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    binary_insertion_sort_from(arr, 1, &mut |a, b| compare(a, b) == Ordering::Less);
+}
+
+/// Binary insertion sort with a key extraction function.
+pub fn binary_insertion_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    binary_insertion_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Binary insertion sort helper which assumes `arr[..sorted]` is already
+/// sorted and inserts the rest of elements into it one by one.
+///
+/// The insertion point is the position after all equal elements, so the sort
+/// is stable. Used by Timsort to extend short natural runs.
+// ANCHOR: binary_insertion_sort
+pub(crate) fn binary_insertion_sort_from<T, F>(arr: &mut [T], sorted: usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    for i in sorted.max(1)..arr.len() {
         let mut j = i;
-        let pos = arr[..i].binary_search(&val).unwrap_or_else(|pos| pos);
+        let pos = {
+            // 1
+            let (sorted, rest) = arr.split_at(i);
+            sorted.partition_point(|x| !is_less(&rest[0], x))
+        };
         // Swap all elements until specific position.
         while j > pos {
-            arr.swap(j - 1, j);
+            // 2
+            swap(arr, j - 1, j);
             j -= 1;
         }
     }
 }
+// ANCHOR_END: binary_insertion_sort
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(insertion_sort);
+    stability_cases!(insertion_sort);
+    by_cases!(insertion_sort_by, insertion_sort_by_key);
 }
 
 #[cfg(test)]
 mod binary_insertion {
     use super::*;
     base_cases!(binary_insertion_sort);
+    stability_cases!(binary_insertion_sort);
+    by_cases!(binary_insertion_sort_by, binary_insertion_sort_by_key);
 }
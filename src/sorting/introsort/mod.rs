@@ -1,4 +1,176 @@
+use std::cmp::Ordering;
+
+use crate::sorting::quicksort::partition_hoare;
+use crate::sorting::stats::{self, swap};
+use crate::sorting::{heapsort_by, insertion_sort_by};
+
+/// Partitions not longer than this are finished by insertion sort.
+const INSERTION_THRESHOLD: usize = 16;
+
 /// Introsort.
-pub fn introsort(_arr: &mut [i32]) {
-    unimplemented!()
+///
+/// Quicksort with Hoare partition scheme and median-of-three pivot, which
+///
+/// - falls back to heapsort when recursion depth exceeds `2 * log2(n)`,
+///   guaranteeing O(n log n) worst case,
+/// - sorts short partitions with insertion sort.
+///
+/// Reference: [Musser, Introspective Sorting and Selection Algorithms][1]
+///
+/// [1]: http://www.cs.rpi.edu/~musser/gp/introsort.ps
+pub fn introsort<T: Ord>(arr: &mut [T]) {
+    introsort_by(arr, T::cmp);
+}
+
+/// Introsort with a comparator function.
+pub fn introsort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    if arr.len() < 2 {
+        return;
+    }
+    introsort_helper(arr, 2 * log2(arr.len()), &mut compare);
+}
+
+/// Introsort with a key extraction function.
+pub fn introsort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    introsort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Floor of base-2 logarithm of a positive integer.
+fn log2(n: usize) -> usize {
+    (usize::BITS - 1 - n.leading_zeros()) as usize
+}
+
+/// Recursion helper with manual tail-call optimization.
+///
+/// * `depth_limit` - Remaining recursion levels before switching to heapsort.
+// ANCHOR: introsort_helper
+fn introsort_helper<T, F>(mut arr: &mut [T], mut depth_limit: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let len = arr.len();
+        if len <= INSERTION_THRESHOLD {
+            // 1
+            insertion_sort_by(arr, &mut *compare);
+            return;
+        }
+        if depth_limit == 0 {
+            // 2
+            heapsort_by(arr, &mut *compare);
+            return;
+        }
+        depth_limit -= 1;
+
+        // Move the median of three to the front as the pivot.
+        median_of_three(arr, 0, len / 2, len - 1, compare); // 3
+        swap(arr, 0, len / 2);
+        let pivot = partition_hoare(arr, 0, len - 1, compare);
+
+        // Recurse into the smaller part, and loop on the larger one.
+        let (left, right) = arr.split_at_mut(pivot + 1);
+        if left.len() < right.len() {
+            // 4
+            introsort_helper(left, depth_limit, compare);
+            arr = right;
+        } else {
+            introsort_helper(right, depth_limit, compare);
+            arr = left;
+        }
+    }
+}
+// ANCHOR_END: introsort_helper
+
+/// Sort `arr[a]`, `arr[b]` and `arr[c]` so that the median lies at `b`.
+pub(crate) fn median_of_three<T, F>(arr: &mut [T], a: usize, b: usize, c: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if compare(&arr[b], &arr[a]) == Ordering::Less {
+        swap(arr, a, b);
+    }
+    if compare(&arr[c], &arr[b]) == Ordering::Less {
+        swap(arr, b, c);
+        if compare(&arr[b], &arr[a]) == Ordering::Less {
+            swap(arr, a, b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod base {
+    use super::*;
+    base_cases!(introsort);
+    by_cases!(introsort_by, introsort_by_key);
+}
+
+#[cfg(test)]
+mod adversarial {
+    use super::*;
+    use crate::sorting::{antiqsort, SortStats};
+
+    /// Musser's median-of-3 killer sequence, which drives median-of-three
+    /// quicksort into quadratic time.
+    fn median_of_3_killer(len: usize) -> Vec<i32> {
+        let k = len / 2;
+        let mut arr = vec![0; 2 * k];
+        for i in 1..=k {
+            if i % 2 == 1 {
+                arr[i - 1] = i as i32;
+                arr[i] = (k + i) as i32;
+            }
+            arr[k + i - 1] = 2 * i as i32;
+        }
+        arr
+    }
+
+    #[test]
+    fn quicksort_killer() {
+        // Without the heapsort fallback, the adversary built against
+        // introsort itself takes Θ(n^2) comparisons.
+        let len = 1 << 16;
+        let n_log_n = len * 16;
+        let inputs = [
+            median_of_3_killer(len),
+            antiqsort(len, |arr, cmp| introsort_by(arr, cmp)),
+        ];
+        for arr in inputs.iter() {
+            let mut res = arr.clone();
+            res.sort();
+            let mut arr = arr.clone();
+            let stats = SortStats::collect(|| introsort(&mut arr));
+            assert_eq!(arr, res);
+            assert!(stats.comparisons < 5 * n_log_n, "{:?}", stats);
+        }
+    }
+
+    #[test]
+    fn heapsort_fallback() {
+        // No recursion budget at all, sorts entirely by heapsort.
+        let mut arr: Vec<_> = (0..1000).map(|_| rand::random::<i32>() % 100).collect();
+        let mut res = arr.clone();
+        res.sort();
+        introsort_helper(&mut arr, 0, &mut i32::cmp);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn random() {
+        for _ in 0..50 {
+            let len = rand::random::<usize>() % 5000;
+            let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % 100).collect();
+            let mut res = arr.clone();
+            res.sort();
+            introsort(&mut arr);
+            assert_eq!(arr, res);
+        }
+    }
 }
@@ -0,0 +1,263 @@
+//! Measures of sortedness.
+//!
+//! How disordered a sequence is tells which sort suits it. Insertion sort
+//! runs in O(n + inversions), and timsort merges existing runs, so few
+//! inversions or few runs favor them over quicksort.
+
+use std::cmp::Ordering;
+
+use crate::sorting::mergesort::merge;
+use crate::sorting::permutation::assert_permutation;
+use crate::sorting::stats;
+
+/// Number of inversions, pairs of elements out of order, in O(n log n).
+///
+/// References to elements are sorted by mergesort. Each merge counts the
+/// inversions between its two piles, which sum up to all inversions.
+///
+/// ```
+/// use rust_algorithm_club::sorting::measures::inversions;
+///
+/// assert_eq!(inversions(&[1, 2, 3]), 0);
+/// assert_eq!(inversions(&[3, 1, 2]), 2);
+/// assert_eq!(inversions(&[3, 2, 1]), 3);
+/// ```
+pub fn inversions<T: Ord>(arr: &[T]) -> usize {
+    inversions_by(arr, T::cmp)
+}
+
+/// Number of inversions with a comparator function.
+pub fn inversions_by<T, F>(arr: &[T], compare: F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let mut compare = |a: &&T, b: &&T| compare(*a, *b);
+    let mut refs: Vec<&T> = arr.iter().collect();
+    stats::record_allocation();
+    let mut buf = Vec::with_capacity(refs.len());
+    stats::record_allocation();
+    inversions_helper(&mut refs, &mut buf, &mut compare)
+}
+
+/// Number of inversions with a key extraction function.
+pub fn inversions_by_key<T, K, F>(arr: &[T], mut key: F) -> usize
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    inversions_by(arr, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Recursion helper, sorting `arr` like mergesort.
+fn inversions_helper<T, F>(arr: &mut [T], buf: &mut Vec<T>, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mid = arr.len() / 2;
+    if mid == 0 {
+        return 0;
+    }
+    inversions_helper(&mut arr[..mid], buf, compare)
+        + inversions_helper(&mut arr[mid..], buf, compare)
+        + merge(arr, mid, buf, compare)
+}
+
+/// Number of maximal non-descending runs. A sorted slice has one run, and
+/// an empty slice has none.
+///
+/// ```
+/// use rust_algorithm_club::sorting::measures::runs;
+///
+/// assert_eq!(runs(&[1, 2, 2, 0, 5, 4]), 3);
+/// ```
+pub fn runs<T: Ord>(arr: &[T]) -> usize {
+    if arr.is_empty() {
+        return 0;
+    }
+    1 + arr.windows(2).filter(|w| w[0] > w[1]).count()
+}
+
+/// Length of the longest non-descending run.
+///
+/// ```
+/// use rust_algorithm_club::sorting::measures::longest_run;
+///
+/// assert_eq!(longest_run(&[1, 2, 2, 0, 5, 4]), 3);
+/// ```
+pub fn longest_run<T: Ord>(arr: &[T]) -> usize {
+    let mut longest = 0;
+    let mut start = 0;
+    for i in 1..=arr.len() {
+        if i == arr.len() || arr[i - 1] > arr[i] {
+            longest = longest.max(i - start);
+            start = i;
+        }
+    }
+    longest
+}
+
+/// Spearman's footrule distance between two permutations of `0..n`.
+///
+/// `p[i]` and `q[i]` are the ranks of item `i`. The distance is the total
+/// displacement of items, the sum of `|p[i] - q[i]|`.
+///
+/// # Panics
+///
+/// Panics if `p` and `q` have different lengths, or either is not a
+/// permutation of `0..n`.
+pub fn spearman_footrule(p: &[usize], q: &[usize]) -> usize {
+    assert_permutations(p, q);
+    p.iter().zip(q).map(|(&a, &b)| a.abs_diff(b)).sum()
+}
+
+/// Kendall tau distance between two permutations of `0..n`, in
+/// O(n log n).
+///
+/// `p[i]` and `q[i]` are the ranks of item `i`. The distance is the number
+/// of pairs of items ranked in different orders, that is, the number of
+/// adjacent swaps to turn one ranking into another.
+///
+/// ```
+/// use rust_algorithm_club::sorting::measures::kendall_tau;
+///
+/// assert_eq!(kendall_tau(&[0, 1, 2], &[0, 1, 2]), 0);
+/// assert_eq!(kendall_tau(&[0, 1, 2], &[2, 1, 0]), 3);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` and `q` have different lengths, or either is not a
+/// permutation of `0..n`.
+pub fn kendall_tau(p: &[usize], q: &[usize]) -> usize {
+    assert_permutations(p, q);
+    // Ranks in `q` of items listed in the order of `p`. Discordant pairs are
+    // its inversions.
+    let mut ranks = vec![0; p.len()];
+    for (&a, &b) in p.iter().zip(q) {
+        ranks[a] = b;
+    }
+    inversions(&ranks)
+}
+
+fn assert_permutations(p: &[usize], q: &[usize]) {
+    assert_eq!(p.len(), q.len(), "permutations must have the same length");
+    assert_permutation(p);
+    assert_permutation(q);
+}
+
+/// Whether a slice is sorted in non-descending order.
+pub fn is_sorted<T: Ord>(arr: &[T]) -> bool {
+    is_sorted_by(arr, T::cmp)
+}
+
+/// Whether a slice is sorted with a comparator function.
+pub fn is_sorted_by<T, F>(arr: &[T], compare: F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    arr.windows(2)
+        .all(|w| compare(&w[0], &w[1]) != Ordering::Greater)
+}
+
+/// Whether a slice is sorted with a key extraction function.
+pub fn is_sorted_by_key<T, K, F>(arr: &[T], mut key: F) -> bool
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    is_sorted_by(arr, |a, b| key(a).cmp(&key(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count inversions by brute force.
+    fn naive_inversions(arr: &[i32]) -> usize {
+        (0..arr.len())
+            .flat_map(|i| (i + 1..arr.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| arr[i] > arr[j])
+            .count()
+    }
+
+    fn random_permutation(len: usize) -> Vec<usize> {
+        let mut perm: Vec<_> = (0..len).collect();
+        for i in (1..len).rev() {
+            perm.swap(i, rand::random::<usize>() % (i + 1));
+        }
+        perm
+    }
+
+    #[test]
+    fn inversions_random() {
+        for len in 0..50 {
+            let arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % 10).collect();
+            assert_eq!(inversions(&arr), naive_inversions(&arr));
+        }
+        let n = 1000;
+        let reversed: Vec<_> = (0..n).rev().collect();
+        assert_eq!(inversions(&reversed), n * (n - 1) / 2);
+    }
+
+    #[test]
+    fn inversions_leave_input() {
+        let arr = [3, 1, 2];
+        assert_eq!(inversions_by(&arr, |a, b| b.cmp(a)), 1);
+        assert_eq!(inversions_by_key(&arr, |v| -v), 1);
+        assert_eq!(arr, [3, 1, 2]);
+    }
+
+    #[test]
+    fn run_measures() {
+        let empty: [i32; 0] = [];
+        assert_eq!((runs(&empty), longest_run(&empty)), (0, 0));
+        assert_eq!((runs(&[1]), longest_run(&[1])), (1, 1));
+        assert_eq!((runs(&[1, 2, 3]), longest_run(&[1, 2, 3])), (1, 3));
+        assert_eq!((runs(&[3, 2, 1]), longest_run(&[3, 2, 1])), (3, 1));
+        let arr = [1, 5, 2, 3, 4, 4, 0];
+        assert_eq!((runs(&arr), longest_run(&arr)), (3, 4));
+    }
+
+    #[test]
+    fn permutation_distances() {
+        assert_eq!(spearman_footrule(&[0, 1, 2], &[2, 1, 0]), 4);
+        assert_eq!(spearman_footrule(&[], &[]), 0);
+        for len in 0..30 {
+            let p = random_permutation(len);
+            let q = random_permutation(len);
+            // Naive Kendall tau by all pairs.
+            let naive = (0..len)
+                .flat_map(|i| (i + 1..len).map(move |j| (i, j)))
+                .filter(|&(i, j)| (p[i] < p[j]) != (q[i] < q[j]))
+                .count();
+            let tau = kendall_tau(&p, &q);
+            assert_eq!(tau, naive);
+            // Diaconis–Graham inequality.
+            let footrule = spearman_footrule(&p, &q);
+            assert!(tau <= footrule && footrule <= 2 * tau);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn not_a_permutation() {
+        kendall_tau(&[0, 0], &[0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "same length")]
+    fn length_mismatch() {
+        spearman_footrule(&[0], &[0, 1]);
+    }
+
+    #[test]
+    fn sorted() {
+        assert!(is_sorted::<i32>(&[]));
+        assert!(is_sorted(&[1, 1, 2]));
+        assert!(!is_sorted(&[2, 1]));
+        assert!(is_sorted_by(&[3, 2, 2], |a, b| b.cmp(a)));
+        assert!(is_sorted_by_key(&["a", "bb", "cc"], |s| s.len()));
+    }
+}
@@ -0,0 +1,536 @@
+use std::cmp::Ordering;
+use std::iter::{FusedIterator, Peekable};
+
+use crate::sorting::stats;
+
+/// Merge two sorted slices into `out`.
+///
+/// Elements are cloned into `out`. Equal elements from `a` come before those
+/// from `b`, so merging is stable.
+///
+/// # Panics
+///
+/// Panics if the length of `out` is not the sum of lengths of `a` and `b`.
+pub fn merge<T: Ord + Clone>(a: &[T], b: &[T], out: &mut [T]) {
+    merge_by(a, b, out, T::cmp);
+}
+
+/// Merge two sorted slices into `out` with a comparator function.
+// ANCHOR: merge_by
+pub fn merge_by<T, F>(a: &[T], b: &[T], out: &mut [T], compare: F)
+where
+    T: Clone,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert_eq!(
+        a.len() + b.len(),
+        out.len(),
+        "out must have the length of a and b combined"
+    );
+    let mut compare = stats::counting(compare);
+    let (mut i, mut j) = (0, 0);
+    for dest in out.iter_mut() {
+        // Take from `a` when equal to keep stability.
+        let take_b = i == a.len() || (j < b.len() && compare(&b[j], &a[i]) == Ordering::Less);
+        if take_b {
+            *dest = b[j].clone();
+            j += 1;
+        } else {
+            *dest = a[i].clone();
+            i += 1;
+        }
+    }
+    stats::record_moves(out.len());
+}
+// ANCHOR_END: merge_by
+
+/// Merge two sorted slices into `out` with a key extraction function.
+pub fn merge_by_key<T, K, F>(a: &[T], b: &[T], out: &mut [T], mut key: F)
+where
+    T: Clone,
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    merge_by(a, b, out, |x, y| key(x).cmp(&key(y)));
+}
+
+/// Lazily merge any number of sorted iterators into one sorted iterator.
+///
+/// Sources are merged through a loser tree, a tournament tree whose internal
+/// nodes remember the loser of each match. Taking the next item replays only
+/// the matches along the path of its source, so each item costs about
+/// log2(k) comparisons for k sources.
+///
+/// Equal items are yielded in the order of their sources, so merging is
+/// stable.
+///
+/// ```
+/// use rust_algorithm_club::sorting::merge_iters;
+///
+/// let shards = vec![vec![1, 4, 7], vec![2, 5], vec![3, 6, 9]];
+/// let merged: Vec<_> = merge_iters(shards).collect();
+/// assert_eq!(merged, [1, 2, 3, 4, 5, 6, 7, 9]);
+/// ```
+pub fn merge_iters<I, T>(
+    iters: I,
+) -> MergeIters<<I::Item as IntoIterator>::IntoIter, impl FnMut(&T, &T) -> Ordering>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator<Item = T>,
+    T: Ord,
+{
+    merge_iters_by(iters, T::cmp)
+}
+
+/// Lazily merge any number of sorted iterators with a comparator function.
+pub fn merge_iters_by<I, T, F>(
+    iters: I,
+    compare: F,
+) -> MergeIters<<I::Item as IntoIterator>::IntoIter, impl FnMut(&T, &T) -> Ordering>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let sources = iters.into_iter().map(IntoIterator::into_iter).collect();
+    MergeIters::new(sources, stats::counting(compare))
+}
+
+/// Lazily merge any number of sorted iterators with a key extraction
+/// function.
+pub fn merge_iters_by_key<I, T, K, F>(
+    iters: I,
+    mut key: F,
+) -> MergeIters<<I::Item as IntoIterator>::IntoIter, impl FnMut(&T, &T) -> Ordering>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator<Item = T>,
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    merge_iters_by(iters, move |a, b| key(a).cmp(&key(b)))
+}
+
+/// Iterator returned by `merge_iters`.
+// ANCHOR: merge_iters
+pub struct MergeIters<I: Iterator, F> {
+    sources: Vec<I>,
+    /// Next item of each source. `None` if the source is exhausted.
+    heads: Vec<Option<I::Item>>,
+    /// Loser tree. `tree[0]` is the source of the overall winner, and
+    /// `tree[n]` is the loser of the match at internal node `n`. Children of
+    /// node `n` are `2n` and `2n + 1`, and source `i` is leaf `k + i`.
+    tree: Vec<usize>,
+    compare: F,
+}
+// ANCHOR_END: merge_iters
+
+impl<I, F> MergeIters<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    fn new(mut sources: Vec<I>, compare: F) -> Self {
+        let heads = sources.iter_mut().map(Iterator::next).collect();
+        stats::record_allocation();
+        let k = sources.len();
+        let mut merge = MergeIters {
+            sources,
+            heads,
+            tree: vec![0; k],
+            compare,
+        };
+        if k == 0 {
+            return merge;
+        }
+
+        // Play all matches bottom-up. `winners[n]` is the winner at node `n`.
+        let mut winners: Vec<_> = (0..2 * k).map(|n| n.wrapping_sub(k)).collect();
+        for n in (1..k).rev() {
+            let (a, b) = (winners[2 * n], winners[2 * n + 1]);
+            let (winner, loser) = if merge.beats(b, a) { (b, a) } else { (a, b) };
+            winners[n] = winner;
+            merge.tree[n] = loser;
+        }
+        merge.tree[0] = if k == 1 { 0 } else { winners[1] };
+        merge
+    }
+
+    /// Whether source `a` wins over source `b`. Exhausted sources always
+    /// lose, and ties go to the smaller index to keep stability.
+    fn beats(&mut self, a: usize, b: usize) -> bool {
+        match (&self.heads[a], &self.heads[b]) {
+            (Some(x), Some(y)) => match (self.compare)(x, y) {
+                Ordering::Less => true,
+                Ordering::Equal => a < b,
+                Ordering::Greater => false,
+            },
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Replay matches from the leaf of `source` up to the root.
+    // ANCHOR: replay
+    fn replay(&mut self, source: usize) {
+        let mut winner = source;
+        let mut node = (source + self.sources.len()) / 2;
+        while node > 0 {
+            if self.beats(self.tree[node], winner) {
+                std::mem::swap(&mut self.tree[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.tree[0] = winner;
+    }
+    // ANCHOR_END: replay
+}
+
+impl<I, F> Iterator for MergeIters<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    // ANCHOR: next
+    fn next(&mut self) -> Option<Self::Item> {
+        let winner = *self.tree.first()?;
+        let item = self.heads[winner].take()?;
+        self.heads[winner] = self.sources[winner].next();
+        self.replay(winner);
+        Some(item)
+    }
+    // ANCHOR_END: next
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let heads = self.heads.iter().filter(|h| h.is_some()).count();
+        self.sources.iter().map(Iterator::size_hint).fold(
+            (heads, Some(heads)),
+            |(lo, hi), (l, h)| {
+                let hi = match (hi, h) {
+                    (Some(hi), Some(h)) => hi.checked_add(h),
+                    _ => None,
+                };
+                (lo.saturating_add(l), hi)
+            },
+        )
+    }
+}
+
+// Exhausted sources are never polled again.
+impl<I, F> FusedIterator for MergeIters<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+}
+
+/// Union of two sorted iterators, like C++ `std::set_union`.
+///
+/// An item appearing `m` times in `a` and `n` times in `b` is yielded
+/// `max(m, n)` times. Equal items are taken from `a` first.
+pub fn union<A, B>(a: A, b: B) -> Union<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    Union {
+        a: a.into_iter().peekable(),
+        b: b.into_iter().peekable(),
+    }
+}
+
+/// Iterator returned by `union`.
+pub struct Union<A: Iterator, B: Iterator> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+}
+
+impl<A, B> Iterator for Union<A, B>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ord = match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match ord {
+            Ordering::Less => self.a.next(),
+            Ordering::Greater => self.b.next(),
+            Ordering::Equal => {
+                self.b.next();
+                self.a.next()
+            }
+        }
+    }
+}
+
+/// Intersection of two sorted iterators, like C++ `std::set_intersection`.
+///
+/// An item appearing `m` times in `a` and `n` times in `b` is yielded
+/// `min(m, n)` times, taken from `a`.
+pub fn intersection<A, B>(a: A, b: B) -> Intersection<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    Intersection {
+        a: a.into_iter().peekable(),
+        b: b.into_iter().peekable(),
+    }
+}
+
+/// Iterator returned by `intersection`.
+pub struct Intersection<A: Iterator, B: Iterator> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+}
+
+// ANCHOR: intersection
+impl<A, B> Iterator for Intersection<A, B>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.cmp(y),
+                _ => return None,
+            };
+            match ord {
+                Ordering::Less => {
+                    self.a.next();
+                }
+                Ordering::Greater => {
+                    self.b.next();
+                }
+                Ordering::Equal => {
+                    self.b.next();
+                    return self.a.next();
+                }
+            }
+        }
+    }
+}
+// ANCHOR_END: intersection
+
+/// Difference of two sorted iterators, like C++ `std::set_difference`.
+///
+/// An item appearing `m` times in `a` and `n` times in `b` is yielded
+/// `max(m - n, 0)` times.
+pub fn difference<A, B>(a: A, b: B) -> Difference<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    Difference {
+        a: a.into_iter().peekable(),
+        b: b.into_iter().peekable(),
+    }
+}
+
+/// Iterator returned by `difference`.
+pub struct Difference<A: Iterator, B: Iterator> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+}
+
+impl<A, B> Iterator for Difference<A, B>
+where
+    A: Iterator,
+    B: Iterator<Item = A::Item>,
+    A::Item: Ord,
+{
+    type Item = A::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => return None,
+            };
+            match ord {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => {
+                    self.b.next();
+                }
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+            }
+        }
+    }
+}
+
+/// Remove consecutive equal items of a sorted iterator, keeping the first
+/// of each run.
+pub fn dedup<I>(iter: I) -> Dedup<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: PartialEq,
+{
+    Dedup {
+        iter: iter.into_iter().peekable(),
+    }
+}
+
+/// Iterator returned by `dedup`.
+pub struct Dedup<I: Iterator> {
+    iter: Peekable<I>,
+}
+
+impl<I> Iterator for Dedup<I>
+where
+    I: Iterator,
+    I::Item: PartialEq,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next()?;
+        while self.iter.next_if_eq(&item).is_some() {}
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_sorted(len: usize) -> Vec<i32> {
+        let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % 20).collect();
+        arr.sort();
+        arr
+    }
+
+    #[test]
+    fn merge_slices() {
+        for &(m, n) in &[(0, 0), (0, 5), (5, 0), (1, 1), (10, 37), (100, 100)] {
+            let (a, b) = (random_sorted(m), random_sorted(n));
+            let mut out = vec![0; m + n];
+            merge(&a, &b, &mut out);
+            let mut res = [a, b].concat();
+            res.sort();
+            assert_eq!(out, res);
+        }
+    }
+
+    #[test]
+    fn merge_stability() {
+        let a = [(1, 'a'), (2, 'b'), (2, 'c')];
+        let b = [(0, 'd'), (2, 'e'), (3, 'f')];
+        let mut out = [(0, ' '); 6];
+        merge_by_key(&a, &b, &mut out, |t| t.0);
+        let values: String = out.iter().map(|t| t.1).collect();
+        assert_eq!(values, "dabcef");
+    }
+
+    #[test]
+    #[should_panic(expected = "length of a and b combined")]
+    fn merge_length_mismatch() {
+        merge(&[1, 2], &[3], &mut [0; 2]);
+    }
+
+    #[test]
+    fn merge_many() {
+        for k in 0..20 {
+            let shards: Vec<_> = (0..k).map(|i| random_sorted(i * 7 % 13)).collect();
+            let mut res = shards.concat();
+            res.sort();
+            let merged = merge_iters(shards);
+            assert_eq!(merged.size_hint(), (res.len(), Some(res.len())));
+            assert_eq!(merged.collect::<Vec<_>>(), res, "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn merge_many_stability() {
+        let shards = vec![
+            vec![(1, 'a'), (3, 'b')],
+            vec![(1, 'c'), (2, 'd'), (3, 'e')],
+            vec![],
+            vec![(1, 'f'), (3, 'g')],
+        ];
+        let values: String = merge_iters_by_key(shards, |t| t.0).map(|t| t.1).collect();
+        assert_eq!(values, "acfdbeg");
+    }
+
+    #[test]
+    fn merge_many_lazily() {
+        // Infinite sources are merged lazily.
+        let evens = (0..).step_by(2);
+        let odds = (1..).step_by(2);
+        let merged: Vec<_> = merge_iters(vec![evens, odds]).take(10).collect();
+        assert_eq!(merged, (0..10).collect::<Vec<_>>());
+
+        let merged: Vec<_> =
+            merge_iters_by(vec![vec![1, 5], vec![9, 2]], |a, b| b.cmp(a)).collect();
+        assert_eq!(merged, [9, 2, 1, 5]);
+    }
+
+    #[test]
+    fn merge_many_comparisons() {
+        // About log2(64) = 6 comparisons per item.
+        let shards: Vec<_> = (0..64).map(|_| random_sorted(100)).collect();
+        let stats = crate::sorting::SortStats::collect(|| {
+            assert_eq!(merge_iters(shards).count(), 6400);
+        });
+        assert!(stats.comparisons <= 64 + 6 * 6400);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = [1, 1, 2, 4, 4, 4, 7];
+        let b = [1, 3, 4, 4, 8];
+        let collect = |iter: &mut dyn Iterator<Item = i32>| iter.collect::<Vec<_>>();
+        assert_eq!(
+            collect(&mut union(a.iter().copied(), b.iter().copied())),
+            [1, 1, 2, 3, 4, 4, 4, 7, 8]
+        );
+        assert_eq!(
+            collect(&mut intersection(a.iter().copied(), b.iter().copied())),
+            [1, 4, 4]
+        );
+        assert_eq!(
+            collect(&mut difference(a.iter().copied(), b.iter().copied())),
+            [1, 2, 4, 7]
+        );
+        assert_eq!(
+            collect(&mut difference(b.iter().copied(), a.iter().copied())),
+            [3, 8]
+        );
+        assert_eq!(collect(&mut dedup(a.iter().copied())), [1, 2, 4, 7]);
+        assert_eq!(collect(&mut union(Vec::new(), b.to_vec())), b);
+        assert_eq!(collect(&mut intersection(a.to_vec(), Vec::new())), []);
+    }
+
+    #[test]
+    fn set_operations_as_sets() {
+        use std::collections::BTreeSet;
+        let (a, b) = (random_sorted(200), random_sorted(150));
+        let (sa, sb): (BTreeSet<_>, BTreeSet<_>) =
+            (a.iter().copied().collect(), b.iter().copied().collect());
+        let union_: Vec<_> = dedup(union(a.clone(), b.clone())).collect();
+        assert!(union_.iter().eq(sa.union(&sb)));
+        let inter: Vec<_> = dedup(intersection(a.clone(), b.clone())).collect();
+        assert!(inter.iter().eq(sa.intersection(&sb)));
+        let diff: Vec<_> = dedup(difference(dedup(a), b)).collect();
+        assert!(diff.iter().eq(sa.difference(&sb)));
+    }
+}
@@ -1,96 +1,383 @@
+use std::cmp::Ordering;
+use std::mem;
+use std::ptr;
+
+use crate::sorting::parallel::{self, ParallelConfig};
+use crate::sorting::{stats, trace};
+
 /// Mergesort.
 ///
 /// - Top-down
 /// - Recursive
-pub fn mergesort(arr: &mut [i32]) {
+pub fn mergesort<T: Ord>(arr: &mut [T]) {
+    mergesort_by(arr, T::cmp);
+}
+
+/// Mergesort with a comparator function.
+// ANCHOR: mergesort
+pub fn mergesort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    // Zero-sized values are indistinguishable, any order is a stable order.
+    if mem::size_of::<T>() == 0 {
+        return;
+    }
+    // Create an array to store intermediate result.
+    let mut buf = Vec::with_capacity(arr.len()); // 1
+    stats::record_allocation();
+    mergesort_helper(arr, &mut buf, &mut compare);
+}
+// ANCHOR_END: mergesort
+
+/// Mergesort with a key extraction function.
+pub fn mergesort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    mergesort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+// ANCHOR: mergesort_helper
+/// Recursion helper
+fn mergesort_helper<T, F>(arr: &mut [T], buf: &mut Vec<T>, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     let mid = arr.len() / 2;
     if mid == 0 {
+        // 2
         return;
     }
 
-    mergesort(&mut arr[..mid]);
-    mergesort(&mut arr[mid..]);
-
-    // Create an array to store intermediate result.
-    let mut ret = arr.to_vec();
+    mergesort_helper(&mut arr[..mid], buf, compare); // 3
+    mergesort_helper(&mut arr[mid..], buf, compare);
 
     // Merge the two piles.
-    merge(&arr[..mid], &arr[mid..], &mut ret[..]);
-
-    // Copy back the result back to original array.
-    arr.copy_from_slice(&ret);
+    merge(arr, mid, buf, compare); // 4
 }
+// ANCHOR_END: mergesort_helper
 
 /// Mergesort bottom-up version.
 ///
 /// - Buttom-up (for array-based data structure)
 /// - Iterative
-pub fn mergesort_bottom_up(arr: &mut [i32]) {
-    let mut width = 1;
+pub fn mergesort_bottom_up<T: Ord>(arr: &mut [T]) {
+    mergesort_bottom_up_by(arr, T::cmp);
+}
+
+/// Mergesort bottom-up version with a comparator function.
+// ANCHOR: mergesort_bottom_up
+pub fn mergesort_bottom_up_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    if mem::size_of::<T>() == 0 {
+        return;
+    }
+    let mut width = 1; // 1
+
     // Create an array to store intermediate result.
-    let mut ret = arr.to_vec();
+    let mut buf = Vec::with_capacity(arr.len()); // 2
+    stats::record_allocation();
     let len = arr.len();
 
     while width < len {
         let mut i = 0;
         while i < len {
             // Check to avoid upper bound and middle index out of bound.
-            let upper = ::std::cmp::min(i + 2 * width, len);
+            let upper = ::std::cmp::min(i + 2 * width, len); // 3
             let mid = ::std::cmp::min(i + width, len);
 
-            merge(&arr[i..mid], &arr[mid..upper], &mut ret[i..upper]);
-
-            // Copy the merged result back to original array.
-            arr[i..upper].copy_from_slice(&ret[i..upper]);
+            // The last subsequence has nothing to merge with.
+            if mid < upper {
+                // 4
+                merge(&mut arr[i..upper], mid - i, &mut buf, &mut compare);
+            }
 
             // Increase start index to merge next two subsequences.
-            i += 2 * width;
+            i += 2 * width; // 5
         }
-        width *= 2;
+        width *= 2; // 6
     }
 }
+// ANCHOR_END: mergesort_bottom_up
 
+/// Mergesort bottom-up version with a key extraction function.
+pub fn mergesort_bottom_up_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    mergesort_bottom_up_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Parallel mergesort.
+///
+/// Two halves are sorted on different threads until running out of workers
+/// or reaching the cutoff, then merged on current thread.
+pub fn parallel_mergesort<T: Ord + Send>(arr: &mut [T], config: ParallelConfig) {
+    parallel_mergesort_by(arr, config, T::cmp);
+}
+
+/// Parallel mergesort with a comparator function.
+pub fn parallel_mergesort_by<T, F>(arr: &mut [T], config: ParallelConfig, compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if mem::size_of::<T>() == 0 {
+        return;
+    }
+    parallel_mergesort_helper(arr, config, &compare);
+}
+
+/// Parallel mergesort with a key extraction function.
+pub fn parallel_mergesort_by_key<T, K, F>(arr: &mut [T], config: ParallelConfig, key: F)
+where
+    T: Send,
+    K: Ord,
+    F: Fn(&T) -> K + Sync,
+{
+    parallel_mergesort_by(arr, config, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Parallel recursion helper
+fn parallel_mergesort_helper<T, F>(arr: &mut [T], config: ParallelConfig, compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if config.is_sequential(arr.len()) {
+        mergesort_by(arr, compare);
+        return;
+    }
+
+    let mid = arr.len() / 2;
+    let (left, right) = arr.split_at_mut(mid);
+    let (left_config, right_config) = config.split();
+    parallel::join(
+        || parallel_mergesort_helper(left, left_config, compare),
+        || parallel_mergesort_helper(right, right_config, compare),
+    );
+
+    // Merge the two piles.
+    let mut buf = Vec::with_capacity(arr.len());
+    stats::record_allocation();
+    merge(arr, mid, &mut buf, &mut stats::counting(compare));
+}
+
+// ANCHOR: merge
 /// Merge helper.
 ///
-/// * `arr1` - Left pile to sort.
-/// * `arr2` - Right pile to sort.
-/// * `ret` - Result array to return
-fn merge(arr1: &[i32], arr2: &[i32], ret: &mut [i32]) {
-    let mut left = 0; // Head of left pile.
-    let mut right = 0; // Head of right pile.
-    let mut index = 0;
-
-    // Compare element and insert back to result array.
-    while left < arr1.len() && right < arr2.len() {
-        if arr1[left] <= arr2[right] {
-            ret[index] = arr1[left];
-            index += 1;
-            left += 1;
-        } else {
-            ret[index] = arr2[right];
-            index += 1;
-            right += 1;
+/// * `arr` - Two piles to merge, `arr[..mid]` and `arr[mid..]`.
+/// * `mid` - Start index of the right pile.
+/// * `buf` - Buffer to store intermediate result.
+/// * `compare` - Comparator function.
+///
+/// Elements are moved into the buffer, then merged back to `arr`. Generic
+/// values cannot be copied like `i32`, so moves are done via raw pointers.
+///
+/// Returns the number of inversions between the two piles, that is, pairs of
+/// a left element greater than a right element. Every time an element is
+/// taken from the right pile, it is less than all the rest of the left pile.
+pub(crate) fn merge<T, F>(arr: &mut [T], mid: usize, buf: &mut Vec<T>, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut inversions = 0;
+    let len = arr.len();
+    buf.reserve(len);
+    // Every element is moved into the buffer and back.
+    stats::record_moves(2 * len);
+    trace::merge(arr, mid);
+
+    // Safety: every element is moved into the buffer, and `MergeHole`
+    // guarantees it is moved back exactly once, even if `compare` panics.
+    // The length of `buf` is always 0, so it never drops any element.
+    unsafe {
+        let v = arr.as_mut_ptr();
+        let b = buf.as_mut_ptr();
+        ptr::copy_nonoverlapping(v, b, len); // 1
+
+        // 2
+        let mut hole = MergeHole {
+            left: b, // Head of left pile.
+            left_end: b.add(mid),
+            right: b.add(mid), // Head of right pile.
+            right_end: b.add(len),
+            dest: v,
+        };
+
+        // Compare element and insert back to result array.
+        while hole.left < hole.left_end && hole.right < hole.right_end {
+            // 3
+            // Take from left pile when equal to keep stability.
+            let src = if compare(&*hole.right, &*hole.left) == Ordering::Less {
+                inversions += hole.left_end.offset_from(hole.left) as usize;
+                let src = hole.right;
+                hole.right = hole.right.add(1);
+                src
+            } else {
+                let src = hole.left;
+                hole.left = hole.left.add(1);
+                src
+            };
+            ptr::copy_nonoverlapping(src, hole.dest, 1);
+            hole.dest = hole.dest.add(1);
         }
+        // 4
+        // `hole` is dropped here, copying the rest elements back.
     }
+    trace::writes(arr);
+    inversions
+}
+// ANCHOR_END: merge
 
-    // Copy the reset elements to returned array.
-    // `memcpy` may be more performant than for-loop assignment.
-    if left < arr1.len() {
-        ret[index..].copy_from_slice(&arr1[left..]);
-    }
-    if right < arr2.len() {
-        ret[index..].copy_from_slice(&arr2[right..]);
+// ANCHOR: merge_hole
+/// Unmerged elements in the buffer.
+///
+/// When dropped, copies the rest of left and right piles to `dest`.
+struct MergeHole<T> {
+    left: *mut T,
+    left_end: *mut T,
+    right: *mut T,
+    right_end: *mut T,
+    dest: *mut T,
+}
+
+impl<T> Drop for MergeHole<T> {
+    fn drop(&mut self) {
+        // Safety: the buffer and the slice never overlap, and `T` is never
+        // zero-sized here.
+        unsafe {
+            let left_len = self.left_end.offset_from(self.left) as usize;
+            ptr::copy_nonoverlapping(self.left, self.dest, left_len);
+            let dest = self.dest.add(left_len);
+            let right_len = self.right_end.offset_from(self.right) as usize;
+            ptr::copy_nonoverlapping(self.right, dest, right_len);
+        }
     }
 }
+// ANCHOR_END: merge_hole
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(mergesort);
+    stability_cases!(mergesort);
+    by_cases!(mergesort_by, mergesort_by_key);
 }
 
 #[cfg(test)]
 mod bottom_up {
     use super::*;
     base_cases!(mergesort_bottom_up);
+    stability_cases!(mergesort_bottom_up);
+    by_cases!(mergesort_bottom_up_by, mergesort_bottom_up_by_key);
+}
+
+#[cfg(test)]
+mod parallel_base {
+    use super::*;
+
+    const CONFIG: ParallelConfig = ParallelConfig {
+        workers: 4,
+        cutoff: 2,
+    };
+
+    fn parallel_mergesort_<T: Ord + Send>(arr: &mut [T]) {
+        parallel_mergesort(arr, CONFIG);
+    }
+
+    fn parallel_mergesort_by_<T: Send>(arr: &mut [T], compare: impl Fn(&T, &T) -> Ordering + Sync) {
+        parallel_mergesort_by(arr, CONFIG, compare);
+    }
+
+    fn parallel_mergesort_by_key_<T: Send, K: Ord>(arr: &mut [T], key: impl Fn(&T) -> K + Sync) {
+        parallel_mergesort_by_key(arr, CONFIG, key);
+    }
+
+    base_cases!(parallel_mergesort_);
+    stability_cases!(parallel_mergesort_);
+    by_cases!(parallel_mergesort_by_, parallel_mergesort_by_key_);
+
+    #[test]
+    fn same_as_sequential() {
+        for &workers in &[1, 2, 3, 8] {
+            let config = ParallelConfig {
+                workers,
+                cutoff: 1000,
+            };
+            // Only a few distinct keys, so stability matters.
+            let arr: Vec<_> = (0..50_000)
+                .map(|i| (rand::random::<u8>() % 16, i))
+                .collect();
+            let mut sequential = arr.clone();
+            mergesort_by_key(&mut sequential, |p| p.0);
+            let mut parallel = arr;
+            parallel_mergesort_by_key(&mut parallel, config, |p| p.0);
+            assert_eq!(parallel, sequential);
+        }
+    }
+
+    #[test]
+    fn stats_from_all_threads() {
+        let arr: Vec<_> = (0..10_000).map(|_| rand::random::<i32>()).collect();
+        let mut sequential = arr.clone();
+        let sequential = crate::sorting::SortStats::collect(|| mergesort(&mut sequential));
+        let mut parallel = arr;
+        let parallel =
+            crate::sorting::SortStats::collect(|| parallel_mergesort(&mut parallel, CONFIG));
+        // Halves are merged the same way as the sequential one.
+        assert_eq!(parallel.comparisons, sequential.comparisons);
+    }
+}
+
+#[cfg(test)]
+mod panic_safety {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// A comparator panics at the `limit`-th comparison.
+    fn panicking_compare(limit: usize) -> impl FnMut(&Box<i32>, &Box<i32>) -> Ordering {
+        let mut count = 0;
+        move |a, b| {
+            count += 1;
+            if count == limit {
+                panic!("comparator panicked");
+            }
+            a.cmp(b)
+        }
+    }
+
+    /// Elements are neither lost nor duplicated if the comparator panics.
+    fn assert_all_present(arr: &[Box<i32>]) {
+        let mut values: Vec<_> = arr.iter().map(|b| **b).collect();
+        values.sort();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn top_down() {
+        let mut arr: Vec<_> = (0..100).rev().map(Box::new).collect();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mergesort_by(&mut arr, panicking_compare(300));
+        }));
+        assert!(result.is_err());
+        assert_all_present(&arr);
+    }
+
+    #[test]
+    fn bottom_up() {
+        let mut arr: Vec<_> = (0..100).rev().map(Box::new).collect();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mergesort_bottom_up_by(&mut arr, panicking_compare(300));
+        }));
+        assert!(result.is_err());
+        assert_all_present(&arr);
+    }
 }
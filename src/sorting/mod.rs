@@ -4,43 +4,118 @@
 #[macro_use]
 mod test_cases;
 
+mod stats;
+pub use self::stats::SortStats;
+
+mod trace;
+pub use self::trace::{count_writes, Frame, SortEvent, Trace};
+
+mod parallel;
+pub use self::parallel::ParallelConfig;
+
+mod adaptive;
+pub use self::adaptive::{
+    explain_sort, sort, sort_by, sort_by_key, sort_with, sort_with_by, Algorithm, Characteristics,
+    Explanation, SortPolicy,
+};
+
+mod generators;
+pub use self::generators::{antiqsort, Pattern, SplitMix64};
+
 mod bubble_sort;
-pub use self::bubble_sort::{bubble_sort, bubble_sort_optimized};
+pub use self::bubble_sort::{
+    bubble_sort, bubble_sort_by, bubble_sort_by_key, bubble_sort_optimized,
+    bubble_sort_optimized_by, bubble_sort_optimized_by_key,
+};
 
 mod insertion_sort;
-pub use self::insertion_sort::{binary_insertion_sort, insertion_sort};
+pub use self::insertion_sort::{
+    binary_insertion_sort, binary_insertion_sort_by, binary_insertion_sort_by_key, insertion_sort,
+    insertion_sort_by, insertion_sort_by_key,
+};
 
 mod selection_sort;
-pub use self::selection_sort::selection_sort;
+pub use self::selection_sort::{
+    cycle_sort, cycle_sort_by, cycle_sort_by_key, selection_sort, selection_sort_by,
+    selection_sort_by_key,
+};
 
 mod shellsort;
-pub use self::shellsort::{shellsort, MARCIN_GAPS};
+pub use self::shellsort::{
+    shellsort, shellsort_by, shellsort_by_key, shellsort_with, shellsort_with_by, Ciura,
+    GapSequence, Knuth, Sedgewick, Shell, Tokuda, MARCIN_GAPS,
+};
+
+pub mod measures;
+
+mod permutation;
+pub use self::permutation::{
+    apply_permutation, argsort, argsort_by, argsort_by_key, argsort_with, argsort_with_by,
+    invert_permutation,
+};
+
+pub(crate) mod mergesort;
+pub use self::mergesort::{
+    mergesort, mergesort_bottom_up, mergesort_bottom_up_by, mergesort_bottom_up_by_key,
+    mergesort_by, mergesort_by_key, parallel_mergesort, parallel_mergesort_by,
+    parallel_mergesort_by_key,
+};
 
-mod mergesort;
-pub use self::mergesort::{mergesort, mergesort_bottom_up};
+mod block_mergesort;
+pub use self::block_mergesort::{block_mergesort, block_mergesort_by, block_mergesort_by_key};
 
-mod heapsort;
-pub use self::heapsort::heapsort;
+pub(crate) mod heapsort;
+pub use self::heapsort::{
+    heapsort, heapsort_by, heapsort_by_key, partial_sort, partial_sort_by, partial_sort_by_key,
+    top_k, top_k_by, top_k_by_key, SortOrder,
+};
 
 mod quicksort;
 pub use self::quicksort::{
-    quicksort, quicksort_3way, quicksort_hoare, quicksort_manual_tco, quicksort_optimized,
+    parallel_quicksort, parallel_quicksort_by, parallel_quicksort_by_key, quicksort,
+    quicksort_3way, quicksort_3way_by, quicksort_3way_by_key, quicksort_3way_string, quicksort_by,
+    quicksort_by_key, quicksort_hoare, quicksort_hoare_by, quicksort_hoare_by_key,
+    quicksort_manual_tco, quicksort_manual_tco_by, quicksort_manual_tco_by_key,
+    quicksort_optimized, quicksort_optimized_by, quicksort_optimized_by_key,
+};
+
+mod sorting_network;
+pub use self::sorting_network::{
+    bitonic_sort, bitonic_sort_by, bitonic_sort_by_key, odd_even_mergesort, odd_even_mergesort_by,
+    odd_even_mergesort_by_key, sort_network, sort_network_by, sort_network_by_key,
 };
 
 mod bucket_sort;
-pub use self::bucket_sort::bucket_sort;
+pub use self::bucket_sort::{bucket_sort, bucket_sort_float, BucketFloat};
 
 mod counting_sort;
-pub use self::counting_sort::counting_sort;
+pub use self::counting_sort::{counting_sort, counting_sort_in_range, counting_sort_into};
 
 mod radix_sort;
-pub use self::radix_sort::radix_sort;
+pub use self::radix_sort::{msd_radix_sort, radix_sort, radix_sort_with, RadixKey};
 
 mod timsort;
-pub use self::timsort::timsort;
+pub use self::timsort::{timsort, timsort_by, timsort_by_key};
 
 mod introsort;
-pub use self::introsort::introsort;
+pub use self::introsort::{introsort, introsort_by, introsort_by_key};
 
 mod pdqsort;
-pub use self::pdqsort::pdqsort;
+pub use self::pdqsort::{pdqsort, pdqsort_by, pdqsort_by_key};
+
+mod external_sort;
+pub use self::external_sort::{
+    external_sort, external_sort_by, external_sort_by_key, RecordFormat,
+};
+
+mod merge;
+pub use self::merge::{
+    dedup, difference, intersection, merge, merge_by, merge_by_key, merge_iters, merge_iters_by,
+    merge_iters_by_key, union, Dedup, Difference, Intersection, MergeIters, Union,
+};
+
+mod select;
+pub use self::select::{
+    nth_element, nth_element_by, nth_element_by_key, quickselect, quickselect_by,
+    quickselect_by_key, select, select_by, select_by_key,
+};
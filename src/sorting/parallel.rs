@@ -0,0 +1,84 @@
+//! Helpers shared by parallel sorting algorithms.
+
+use std::panic;
+use std::thread;
+
+use crate::sorting::stats;
+use crate::sorting::SortStats;
+
+/// Configuration of parallel sorts.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{parallel_mergesort, ParallelConfig};
+///
+/// let mut arr: Vec<_> = (0..100_000).rev().collect();
+/// let config = ParallelConfig { workers: 4, ..Default::default() };
+/// parallel_mergesort(&mut arr, config);
+/// assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParallelConfig {
+    /// Maximum number of threads sorting at the same time.
+    pub workers: usize,
+    /// Slices not longer than this are sorted sequentially.
+    pub cutoff: usize,
+}
+
+impl Default for ParallelConfig {
+    /// As many workers as available CPUs, and a cutoff of 8192 elements.
+    fn default() -> Self {
+        ParallelConfig {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            cutoff: 8192,
+        }
+    }
+}
+
+impl ParallelConfig {
+    /// Whether a slice of `len` should be sorted on current thread only.
+    pub(crate) fn is_sequential(&self, len: usize) -> bool {
+        self.workers < 2 || len <= self.cutoff
+    }
+
+    /// Split workers for two halves of work.
+    pub(crate) fn split(&self) -> (ParallelConfig, ParallelConfig) {
+        let half = self.workers / 2;
+        let left = ParallelConfig {
+            workers: half,
+            ..*self
+        };
+        let right = ParallelConfig {
+            workers: self.workers - half,
+            ..*self
+        };
+        (left, right)
+    }
+}
+
+/// Run `a` on a new thread and `b` on current thread, and wait for both.
+///
+/// Statistics are thread-local, so those of the new thread are collected
+/// and added to current thread, if current thread is collecting. Traces are
+/// not recorded on the new thread.
+pub(crate) fn join<A, B>(a: A, b: B)
+where
+    A: FnOnce() + Send,
+    B: FnOnce(),
+{
+    let collecting = stats::active();
+    thread::scope(|s| {
+        let handle = s.spawn(move || {
+            if collecting {
+                SortStats::collect(a)
+            } else {
+                a();
+                SortStats::default()
+            }
+        });
+        b();
+        match handle.join() {
+            Ok(collected) => stats::record_stats(collected),
+            Err(e) => panic::resume_unwind(e),
+        }
+    });
+}
@@ -1,4 +1,298 @@
+use std::cmp::Ordering;
+
+use crate::sorting::introsort::median_of_three;
+use crate::sorting::quicksort::partition_3way;
+use crate::sorting::stats::{self, swap};
+use crate::sorting::trace;
+use crate::sorting::{heapsort_by, insertion_sort_by};
+
+/// Partitions shorter than this are sorted by insertion sort.
+const INSERTION_THRESHOLD: usize = 24;
+
+/// Partitions longer than this use Tukey's ninther as the pivot.
+const NINTHER_THRESHOLD: usize = 128;
+
+/// Maximum number of element moves allowed in a partial insertion sort.
+const PARTIAL_INSERTION_LIMIT: usize = 8;
+
 /// Pattern-defeating quicksort.
-pub fn pdqsort(_arr: &mut [i32]) {
-    unimplemented!()
+///
+/// An introsort variant by Orson Peters, which
+///
+/// - chooses pivot by median-of-three, or ninther on large partitions,
+/// - detects already partitioned input and tries a partial insertion sort,
+/// - breaks patterns by swapping elements after an unbalanced partition,
+/// - groups elements equal to pivot with 3-way partition when many
+///   duplicates are detected,
+/// - falls back to heapsort after too many unbalanced partitions.
+///
+/// Reference: [orlp/pdqsort](https://github.com/orlp/pdqsort)
+pub fn pdqsort<T: Ord>(arr: &mut [T]) {
+    pdqsort_by(arr, T::cmp);
+}
+
+/// Pattern-defeating quicksort with a comparator function.
+pub fn pdqsort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let len = arr.len();
+    if len < 2 {
+        return;
+    }
+    let bad_allowed = (usize::BITS - len.leading_zeros()) as usize;
+    pdqsort_helper(arr, None, bad_allowed, &mut compare);
+}
+
+/// Pattern-defeating quicksort with a key extraction function.
+pub fn pdqsort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    pdqsort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Recursion helper.
+///
+/// * `pred` - The element right before `arr` in original slice, which is no
+///   greater than any element in `arr`. `None` if `arr` is leftmost.
+/// * `bad_allowed` - Remaining unbalanced partitions before switching to
+///   heapsort.
+fn pdqsort_helper<'a, T, F>(
+    mut arr: &'a mut [T],
+    mut pred: Option<&'a T>,
+    mut bad_allowed: usize,
+    compare: &mut F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let len = arr.len();
+        if len < INSERTION_THRESHOLD {
+            insertion_sort_by(arr, &mut *compare);
+            return;
+        }
+
+        // 1. Choose pivot and move it to the front.
+        let half = len / 2;
+        if len > NINTHER_THRESHOLD {
+            median_of_three(arr, 0, half, len - 1, compare);
+            median_of_three(arr, 1, half - 1, len - 2, compare);
+            median_of_three(arr, 2, half + 1, len - 3, compare);
+            median_of_three(arr, half - 1, half, half + 1, compare);
+        } else {
+            median_of_three(arr, 0, half, len - 1, compare);
+        }
+        swap(arr, 0, half);
+
+        // 2. If pivot equals to predecessor, there are many duplicates.
+        // Elements equal to pivot are grouped by 3-way partition and never
+        // touched again. Only larger elements are left to sort.
+        if let Some(p) = pred {
+            if compare(p, &arr[0]) != Ordering::Less {
+                swap(arr, 0, len - 1);
+                let (_, larger) = partition_3way(arr, 0, len as isize - 1, compare);
+                arr = &mut arr[(larger + 1) as usize..];
+                continue;
+            }
+        }
+
+        // 3. Partition and check balance.
+        let (mid, was_partitioned) = partition_right(arr, compare);
+        let (left, rest) = arr.split_at_mut(mid);
+        let (pivot, right) = rest.split_first_mut().unwrap();
+        let pivot: &T = pivot;
+
+        if left.len() < len / 8 || right.len() < len / 8 {
+            // 4.1 Too many bad choices, fall back to heapsort.
+            bad_allowed -= 1;
+            if bad_allowed == 0 {
+                heapsort_by(left, &mut *compare);
+                heapsort_by(right, &mut *compare);
+                return;
+            }
+            break_patterns(left);
+            break_patterns(right);
+        } else if was_partitioned
+            && partial_insertion_sort(left, compare)
+            && partial_insertion_sort(right, compare)
+        {
+            // 4.2 Balanced, no swap happened, and both sides are nearly
+            // sorted. Likely the input is sorted already.
+            return;
+        }
+
+        // 5. Recurse into the shorter side, and loop on the longer one.
+        if left.len() < right.len() {
+            pdqsort_helper(left, pred, bad_allowed, compare);
+            arr = right;
+            pred = Some(pivot);
+        } else {
+            pdqsort_helper(right, Some(pivot), bad_allowed, compare);
+            arr = left;
+        }
+    }
+}
+
+/// Partition with `arr[0]` as pivot. Elements less than pivot go left,
+/// others go right.
+///
+/// Return the final index of the pivot, and whether the slice was already
+/// partitioned (no swap happened).
+fn partition_right<T, F>(arr: &mut [T], compare: &mut F) -> (usize, bool)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // The pivot stays at front until partitioning completes.
+    trace::pivot(&arr[0]);
+    let (pivot, rest) = arr.split_first_mut().unwrap();
+    let mut is_less = |x: &T| compare(x, pivot) == Ordering::Less;
+    let mut l = 0;
+    let mut r = rest.len();
+
+    // Invariant: rest[..l] < pivot and rest[r..] >= pivot.
+    while l < r && is_less(&rest[l]) {
+        l += 1;
+    }
+    while l < r && !is_less(&rest[r - 1]) {
+        r -= 1;
+    }
+    let was_partitioned = l >= r;
+
+    while l < r {
+        // Two elements are misplaced, swap them.
+        swap(rest, l, r - 1);
+        l += 1;
+        r -= 1;
+        while l < r && is_less(&rest[l]) {
+            l += 1;
+        }
+        while l < r && !is_less(&rest[r - 1]) {
+            r -= 1;
+        }
+    }
+
+    // Swap pivot to the middle of two piles.
+    swap(arr, 0, l);
+    (l, was_partitioned)
+}
+
+/// Insertion sort which gives up after moving too many elements.
+///
+/// Return `true` if the slice is completely sorted.
+fn partial_insertion_sort<T, F>(arr: &mut [T], compare: &mut F) -> bool
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut moves = 0;
+    for i in 1..arr.len() {
+        let mut j = i;
+        while j > 0 && compare(&arr[j - 1], &arr[j]) == Ordering::Greater {
+            swap(arr, j - 1, j);
+            j -= 1;
+        }
+        moves += i - j;
+        if moves > PARTIAL_INSERTION_LIMIT {
+            return false;
+        }
+    }
+    true
+}
+
+/// Swap some elements around the middle to random positions, breaking
+/// patterns which may cause unbalanced partitions.
+fn break_patterns<T>(arr: &mut [T]) {
+    let len = arr.len();
+    if len < 8 {
+        return;
+    }
+
+    // Xorshift pseudo random generator seeded by length, deterministic.
+    let mut seed = len as u32;
+    let mut gen_u32 = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+
+    let modulus = len.next_power_of_two();
+    let pos = len / 4 * 2;
+    for i in 0..3 {
+        let mut other = gen_u32() as usize & (modulus - 1);
+        if other >= len {
+            other -= len;
+        }
+        swap(arr, pos - 1 + i, other);
+    }
+}
+
+#[cfg(test)]
+mod base {
+    use super::*;
+    base_cases!(pdqsort);
+    by_cases!(pdqsort_by, pdqsort_by_key);
+}
+
+#[cfg(test)]
+mod patterns {
+    use super::*;
+
+    const LEN: i32 = 10000;
+
+    fn assert(mut arr: Vec<i32>) {
+        let mut res = arr.clone();
+        res.sort();
+        pdqsort(&mut arr);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn sorted() {
+        assert((0..LEN).collect());
+    }
+
+    #[test]
+    fn reversed() {
+        assert((0..LEN).rev().collect());
+    }
+
+    #[test]
+    fn sawtooth() {
+        assert((0..LEN).map(|i| i % 100).collect());
+    }
+
+    #[test]
+    fn organ_pipe() {
+        assert((0..LEN / 2).chain((0..LEN / 2).rev()).collect());
+    }
+
+    #[test]
+    fn all_equal() {
+        assert(vec![7; LEN as usize]);
+    }
+
+    #[test]
+    fn few_unique() {
+        assert((0..LEN).map(|_| rand::random::<i32>() % 4).collect());
+    }
+
+    #[test]
+    fn random() {
+        for _ in 0..50 {
+            let len = rand::random::<usize>() % 5000;
+            assert((0..len).map(|_| rand::random::<i32>()).collect());
+        }
+    }
+
+    #[test]
+    fn heapsort_fallback() {
+        let mut arr: Vec<_> = (0..1000).map(|_| rand::random::<i32>() % 100).collect();
+        let mut res = arr.clone();
+        res.sort();
+        pdqsort_helper(&mut arr, None, 1, &mut i32::cmp);
+        assert_eq!(arr, res);
+    }
 }
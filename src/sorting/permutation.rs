@@ -0,0 +1,275 @@
+//! Sort permutations.
+//!
+//! A permutation `perm` of `arr` lists indices of `arr` in a new order, that
+//! is, `perm[i]` is the index of the element moving to position `i`.
+//! Sorting indices instead of elements lets several parallel columns be
+//! reordered the same way.
+
+use std::cmp::Ordering;
+
+use crate::sorting::mergesort_by;
+use crate::sorting::stats::{self, swap};
+
+/// Indices which sort `arr`, keeping equal elements in their order.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{apply_permutation, argsort};
+///
+/// let mut names = vec!["carol", "alice", "bob"];
+/// let mut ages = vec![35, 20, 35];
+/// let perm = argsort(&ages);
+/// assert_eq!(perm, vec![1, 0, 2]);
+///
+/// apply_permutation(&mut names, &perm);
+/// apply_permutation(&mut ages, &perm);
+/// assert_eq!(names, vec!["alice", "carol", "bob"]);
+/// assert_eq!(ages, vec![20, 35, 35]);
+/// ```
+pub fn argsort<T: Ord>(arr: &[T]) -> Vec<usize> {
+    argsort_by(arr, T::cmp)
+}
+
+/// Indices which sort `arr` with a comparator function.
+pub fn argsort_by<T, F>(arr: &[T], compare: F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    argsort_with_by(arr, |idx, compare| mergesort_by(idx, compare), compare)
+}
+
+/// Indices which sort `arr` with a key extraction function.
+pub fn argsort_by_key<T, K, F>(arr: &[T], mut key: F) -> Vec<usize>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argsort_by(arr, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Indices which sort `arr`, sorted by the given comparison sort.
+///
+/// Equal elements are ordered by their indices, so the result is stable
+/// even if `sort` is not.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{argsort_with, heapsort_by};
+///
+/// let perm = argsort_with(&[2, 1, 2, 1], |idx, compare| heapsort_by(idx, compare));
+/// assert_eq!(perm, vec![1, 3, 0, 2]);
+/// ```
+pub fn argsort_with<T, S>(arr: &[T], sort: S) -> Vec<usize>
+where
+    T: Ord,
+    S: FnOnce(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering),
+{
+    argsort_with_by(arr, sort, T::cmp)
+}
+
+/// Indices which sort `arr` with a comparator function, sorted by the given
+/// comparison sort.
+pub fn argsort_with_by<T, S, F>(arr: &[T], sort: S, compare: F) -> Vec<usize>
+where
+    S: FnOnce(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering),
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let mut perm: Vec<usize> = (0..arr.len()).collect();
+    stats::record_allocation();
+    sort(&mut perm, &mut |&a, &b| {
+        compare(&arr[a], &arr[b]).then(a.cmp(&b))
+    });
+    perm
+}
+
+/// Reorder `arr` by `perm` in place, so that `arr[i]` becomes the element
+/// at `perm[i]`.
+///
+/// Each cycle of the permutation is followed from its smallest index,
+/// swapping elements into place, so at most `n - 1` swaps are made. Only a
+/// flag per element is allocated to mark visited positions.
+///
+/// # Panics
+///
+/// Panics if `perm` is not a permutation of the indices of `arr`.
+pub fn apply_permutation<T>(arr: &mut [T], perm: &[usize]) {
+    assert_eq!(
+        arr.len(),
+        perm.len(),
+        "permutation must have the length of arr"
+    );
+    assert_permutation(perm);
+    let mut visited = vec![false; perm.len()];
+    for start in 0..perm.len() {
+        if visited[start] {
+            continue;
+        }
+        // `arr[j]` holds the element originally at `start` until the cycle
+        // closes.
+        let mut j = start;
+        loop {
+            visited[j] = true;
+            let k = perm[j];
+            if k == start {
+                break;
+            }
+            swap(arr, j, k);
+            j = k;
+        }
+    }
+}
+
+/// Inverse of a permutation, mapping each index to its position in `perm`.
+///
+/// Applying a permutation and then its inverse restores the original order.
+/// The inverse of `argsort(arr)` gives the rank of each element.
+///
+/// # Panics
+///
+/// Panics if `perm` is not a permutation.
+pub fn invert_permutation(perm: &[usize]) -> Vec<usize> {
+    assert_permutation(perm);
+    let mut inverse = vec![0; perm.len()];
+    for (i, &p) in perm.iter().enumerate() {
+        inverse[p] = i;
+    }
+    inverse
+}
+
+/// Panic if `perm` does not contain each of `0..perm.len()` exactly once.
+pub(crate) fn assert_permutation(perm: &[usize]) {
+    let mut seen = vec![false; perm.len()];
+    for &i in perm.iter() {
+        assert!(i < perm.len() && !seen[i], "not a permutation");
+        seen[i] = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::*;
+
+    type SortBy = fn(&mut [usize], &mut dyn FnMut(&usize, &usize) -> Ordering);
+
+    /// Every comparison sort with a comparator version.
+    const ALGOS: &[(&str, SortBy)] = &[
+        ("bubble_sort", |a, c| bubble_sort_by(a, c)),
+        ("bubble_sort_optimized", |a, c| {
+            bubble_sort_optimized_by(a, c)
+        }),
+        ("insertion_sort", |a, c| insertion_sort_by(a, c)),
+        ("binary_insertion_sort", |a, c| {
+            binary_insertion_sort_by(a, c)
+        }),
+        ("selection_sort", |a, c| selection_sort_by(a, c)),
+        ("cycle_sort", |a, c| cycle_sort_by(a, c)),
+        ("shellsort", |a, c| shellsort_by(a, c)),
+        ("heapsort", |a, c| heapsort_by(a, c)),
+        ("mergesort", |a, c| mergesort_by(a, c)),
+        ("mergesort_bottom_up", |a, c| mergesort_bottom_up_by(a, c)),
+        ("block_mergesort", |a, c| block_mergesort_by(a, c)),
+        ("quicksort", |a, c| quicksort_by(a, c)),
+        ("quicksort_optimized", |a, c| quicksort_optimized_by(a, c)),
+        ("quicksort_manual_tco", |a, c| quicksort_manual_tco_by(a, c)),
+        ("quicksort_3way", |a, c| quicksort_3way_by(a, c)),
+        ("quicksort_hoare", |a, c| quicksort_hoare_by(a, c)),
+        ("timsort", |a, c| timsort_by(a, c)),
+        ("introsort", |a, c| introsort_by(a, c)),
+        ("pdqsort", |a, c| pdqsort_by(a, c)),
+        ("sort", |a, c| sort_by(a, c)),
+    ];
+
+    /// Stable sort of indices by the standard library.
+    fn expected<T: Ord>(arr: &[T]) -> Vec<usize> {
+        let mut perm: Vec<usize> = (0..arr.len()).collect();
+        perm.sort_by_key(|&i| &arr[i]);
+        perm
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(argsort::<i32>(&[]), Vec::<usize>::new());
+        assert_eq!(invert_permutation(&[]), Vec::<usize>::new());
+        apply_permutation::<i32>(&mut [], &[]);
+    }
+
+    #[test]
+    fn stable_with_every_sort() {
+        for pattern in Pattern::ALL.iter() {
+            for &len in &[1, 2, 10, 500] {
+                // Few distinct keys so that stability matters.
+                let arr: Vec<i32> = pattern.generate(len, 3).iter().map(|v| v % 8).collect();
+                let res = expected(&arr);
+                for (name, algo) in ALGOS {
+                    let perm = argsort_with(&arr, algo);
+                    assert_eq!(perm, res, "{} on {:?}", name, pattern);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn by() {
+        let arr = [3, -1, 2, -3, 1];
+        assert_eq!(argsort(&arr), vec![3, 1, 4, 2, 0]);
+        assert_eq!(argsort_by(&arr, |a, b| b.cmp(a)), vec![0, 2, 4, 1, 3]);
+        assert_eq!(argsort_by_key(&arr, |v: &i32| v.abs()), vec![1, 4, 2, 0, 3]);
+    }
+
+    #[test]
+    fn apply_sorts() {
+        for pattern in Pattern::ALL.iter() {
+            let arr = pattern.generate(1000, 4);
+            let mut res = arr.clone();
+            res.sort();
+            let mut sorted = arr.clone();
+            apply_permutation(&mut sorted, &argsort(&arr));
+            assert_eq!(sorted, res);
+        }
+    }
+
+    #[test]
+    fn inverse_restores() {
+        let arr = Pattern::Random.generate(1000, 5);
+        let perm = argsort(&arr);
+        let inverse = invert_permutation(&perm);
+        assert_permutation(&inverse);
+        assert_eq!(invert_permutation(&inverse), perm);
+
+        let mut restored = arr.clone();
+        apply_permutation(&mut restored, &perm);
+        apply_permutation(&mut restored, &inverse);
+        assert_eq!(restored, arr);
+
+        // The inverse of argsort gives ranks.
+        let ranks = invert_permutation(&argsort(&[30, 10, 20]));
+        assert_eq!(ranks, vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn apply_swaps_per_cycle() {
+        // Cycles (0 1 2) and (3 4), and a fixed point 5.
+        let mut arr = ['a', 'b', 'c', 'd', 'e', 'f'];
+        let stats = SortStats::collect(|| apply_permutation(&mut arr, &[1, 2, 0, 4, 3, 5]));
+        assert_eq!(arr, ['b', 'c', 'a', 'e', 'd', 'f']);
+        assert_eq!(stats.swaps, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn duplicate_index() {
+        apply_permutation(&mut [1, 2, 3], &[0, 1, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a permutation")]
+    fn out_of_bounds() {
+        invert_permutation(&[0, 3, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "permutation must have the length of arr")]
+    fn length_mismatch() {
+        apply_permutation(&mut [1, 2, 3], &[0, 1]);
+    }
+}
@@ -1,156 +1,417 @@
+use std::cmp::Ordering;
+
+use crate::sorting::parallel::{self, ParallelConfig};
+use crate::sorting::radix_sort::byte_at;
+use crate::sorting::stats::{self, swap};
+use crate::sorting::trace;
+
 // -------------------------------------
 // Lomuto partition scheme
 // -------------------------------------
 
 /// Quicksort with Lomuto parition scheme.
-pub fn quicksort(arr: &mut [i32]) {
+pub fn quicksort<T: Ord>(arr: &mut [T]) {
+    quicksort_by(arr, T::cmp);
+}
+
+/// Quicksort with Lomuto parition scheme and a comparator function.
+// ANCHOR: quicksort
+pub fn quicksort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let hi = arr.len() as isize - 1;
-    quicksort_helper(arr, 0, hi);
+    quicksort_helper(arr, 0, hi, &mut compare);
+}
+// ANCHOR_END: quicksort
+
+/// Quicksort with Lomuto parition scheme and a key extraction function.
+pub fn quicksort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quicksort_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
+// ANCHOR: quicksort_helper
 /// Recursion helper
-fn quicksort_helper(arr: &mut [i32], lo: isize, hi: isize) {
+fn quicksort_helper<T, F>(arr: &mut [T], lo: isize, hi: isize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     if lo <= hi {
-        let pivot = partition(arr, lo, hi);
-        quicksort_helper(arr, lo, pivot - 1);
-        quicksort_helper(arr, pivot + 1, hi);
+        // 1
+        let pivot = partition(arr, lo, hi, compare); // 2
+        quicksort_helper(arr, lo, pivot - 1, compare); // 3
+        quicksort_helper(arr, pivot + 1, hi, compare); // 4
     }
 }
+// ANCHOR_END: quicksort_helper
 
 /// Tail-call opitimized quicksort with Lomuto parition scheme.
-pub fn quicksort_optimized(arr: &mut [i32]) {
+pub fn quicksort_optimized<T: Ord>(arr: &mut [T]) {
+    quicksort_optimized_by(arr, T::cmp);
+}
+
+/// Tail-call opitimized quicksort with Lomuto parition scheme and a
+/// comparator function.
+pub fn quicksort_optimized_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let hi = arr.len() as isize - 1;
-    quicksort_helper_optimized(arr, 0, hi);
+    quicksort_helper_optimized(arr, 0, hi, &mut compare);
+}
+
+/// Tail-call opitimized quicksort with Lomuto parition scheme and a key
+/// extraction function.
+pub fn quicksort_optimized_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quicksort_optimized_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
 /// Tail-call optimized recursion helper.
 ///
 /// Can achieve O(log n) auxiliary space complexity if Rust compiler is
 /// implemented with tail-call optimization in the future).
-fn quicksort_helper_optimized(arr: &mut [i32], lo: isize, hi: isize) {
+// ANCHOR: quicksort_helper_optimized
+fn quicksort_helper_optimized<T, F>(arr: &mut [T], lo: isize, hi: isize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     if lo <= hi {
-        let pivot = partition(arr, lo, hi);
+        let pivot = partition(arr, lo, hi, compare);
         if pivot - lo < hi - pivot {
             // 1
-            quicksort_helper_optimized(arr, lo, pivot - 1);
-            quicksort_helper_optimized(arr, pivot + 1, hi);
+            quicksort_helper_optimized(arr, lo, pivot - 1, compare);
+            quicksort_helper_optimized(arr, pivot + 1, hi, compare); // 2
         } else {
-            quicksort_helper_optimized(arr, pivot + 1, hi);
-            quicksort_helper_optimized(arr, lo, pivot - 1);
+            quicksort_helper_optimized(arr, pivot + 1, hi, compare);
+            quicksort_helper_optimized(arr, lo, pivot - 1, compare); // 3
         }
     }
 }
+// ANCHOR_END: quicksort_helper_optimized
 
 /// Manual tail-call opitimized quicksort with Lomuto parition scheme.
-pub fn quicksort_manual_tco(arr: &mut [i32]) {
+pub fn quicksort_manual_tco<T: Ord>(arr: &mut [T]) {
+    quicksort_manual_tco_by(arr, T::cmp);
+}
+
+/// Manual tail-call opitimized quicksort with Lomuto parition scheme and a
+/// comparator function.
+pub fn quicksort_manual_tco_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let hi = arr.len() as isize - 1;
-    quicksort_helper_manual_tco(arr, 0, hi);
+    quicksort_helper_manual_tco(arr, 0, hi, &mut compare);
+}
+
+/// Manual tail-call opitimized quicksort with Lomuto parition scheme and a
+/// key extraction function.
+pub fn quicksort_manual_tco_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quicksort_manual_tco_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
 /// Manual tail-call opitimized recursion helper.
 ///
 /// Can achieve O(log n) auxiliary space complexity without any
 /// compiler-optimization of tail-call.
-fn quicksort_helper_manual_tco(arr: &mut [i32], mut lo: isize, mut hi: isize) {
+// ANCHOR: quicksort_helper_manual_tco
+fn quicksort_helper_manual_tco<T, F>(arr: &mut [T], mut lo: isize, mut hi: isize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     while lo < hi {
-        let pivot = partition(arr, lo, hi);
+        let pivot = partition(arr, lo, hi, compare);
         if pivot - lo < hi - pivot {
-            quicksort_helper_manual_tco(arr, lo, pivot - 1);
+            quicksort_helper_manual_tco(arr, lo, pivot - 1, compare);
             lo = pivot + 1;
         } else {
-            quicksort_helper_manual_tco(arr, pivot + 1, hi);
+            quicksort_helper_manual_tco(arr, pivot + 1, hi, compare);
             hi = pivot - 1;
         }
     }
 }
+// ANCHOR_END: quicksort_helper_manual_tco
 
 /// Lomuto partition scheme
 ///
 /// Return index of the pivot.
-fn partition(arr: &mut [i32], lo: isize, hi: isize) -> isize {
+// ANCHOR: partition
+fn partition<T, F>(arr: &mut [T], lo: isize, hi: isize, compare: &mut F) -> isize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     // -- Determine the pivot --
     // In Lomuto parition scheme,
     // the latest element is always chosen as the pivot.
-    let pivot = arr[hi as usize];
+    // It stays at `hi` until the end of partitioning.
+    let pivot = hi as usize; // 1
+    trace::pivot(&arr[pivot]);
     let mut i = lo;
 
     // -- Swap elements --
     for j in lo..hi {
-        if arr[j as usize] < pivot {
-            arr.swap(i as usize, j as usize);
-            i += 1;
+        // 2
+        if compare(&arr[j as usize], &arr[pivot]) == Ordering::Less {
+            swap(arr, i as usize, j as usize);
+            i += 1; // 3
         }
     }
     // Swap pivot to the middle of two piles.
-    arr.swap(i as usize, hi as usize);
+    swap(arr, i as usize, hi as usize); // 4
     i // Return the final index of the pivot
 }
+// ANCHOR_END: partition
+
+/// Parallel quicksort with Lomuto partition scheme.
+///
+/// Two sides of a partition are sorted on different threads until running
+/// out of workers or reaching the cutoff, then sequentially on each thread.
+/// Partitions are the same as those of `quicksort`, so is the result, even
+/// the order of equal elements. So is the O(n^2) worst case on sorted input.
+pub fn parallel_quicksort<T: Ord + Send>(arr: &mut [T], config: ParallelConfig) {
+    parallel_quicksort_by(arr, config, T::cmp);
+}
+
+/// Parallel quicksort with Lomuto partition scheme and a comparator function.
+pub fn parallel_quicksort_by<T, F>(arr: &mut [T], config: ParallelConfig, compare: F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    parallel_quicksort_helper(arr, config, &compare);
+}
+
+/// Parallel quicksort with Lomuto partition scheme and a key extraction
+/// function.
+pub fn parallel_quicksort_by_key<T, K, F>(arr: &mut [T], config: ParallelConfig, key: F)
+where
+    T: Send,
+    K: Ord,
+    F: Fn(&T) -> K + Sync,
+{
+    parallel_quicksort_by(arr, config, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Parallel recursion helper
+fn parallel_quicksort_helper<T, F>(arr: &mut [T], config: ParallelConfig, compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let len = arr.len();
+    let mut compare_mut = stats::counting(compare);
+    if config.is_sequential(len) || len < 2 {
+        // Recursion on the larger side is a loop, as sorted input recurses
+        // once per element.
+        quicksort_helper_manual_tco(arr, 0, len as isize - 1, &mut compare_mut);
+        return;
+    }
+    let pivot = partition(arr, 0, len as isize - 1, &mut compare_mut) as usize;
+
+    let (left, rest) = arr.split_at_mut(pivot);
+    let right = &mut rest[1..];
+    let (left_config, right_config) = config.split();
+    parallel::join(
+        || parallel_quicksort_helper(left, left_config, compare),
+        || parallel_quicksort_helper(right, right_config, compare),
+    );
+}
 
 // -------------------------------------
 // 3-way partition scheme
 // -------------------------------------
 
 /// Quicksort with 3-way parition scheme.
-pub fn quicksort_3way(arr: &mut [i32]) {
+pub fn quicksort_3way<T: Ord>(arr: &mut [T]) {
+    quicksort_3way_by(arr, T::cmp);
+}
+
+/// Quicksort with 3-way parition scheme and a comparator function.
+pub fn quicksort_3way_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let hi = arr.len() as isize - 1;
-    quicksort_helper_3way(arr, 0, hi);
+    quicksort_helper_3way(arr, 0, hi, &mut compare);
+}
+
+/// Quicksort with 3-way parition scheme and a key extraction function.
+pub fn quicksort_3way_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quicksort_3way_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
 /// Recursion helper
-fn quicksort_helper_3way(arr: &mut [i32], lo: isize, hi: isize) {
+fn quicksort_helper_3way<T, F>(arr: &mut [T], lo: isize, hi: isize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     if lo <= hi {
-        let (smaller, larger) = partition_3way(arr, lo, hi);
-        quicksort_helper_3way(arr, lo, smaller - 1);
-        quicksort_helper_3way(arr, larger + 1, hi);
+        let (smaller, larger) = partition_3way(arr, lo, hi, compare);
+        quicksort_helper_3way(arr, lo, smaller - 1, compare);
+        quicksort_helper_3way(arr, larger + 1, hi, compare);
     }
 }
 
 /// 3-way paritition scheme
 ///
 /// Return smaller and larger index. (to avoid redundant work on identical elements)
-fn partition_3way(arr: &mut [i32], lo: isize, hi: isize) -> (isize, isize) {
-    let pivot = arr[hi as usize];
+// ANCHOR: partition_3way
+pub(crate) fn partition_3way<T, F>(
+    arr: &mut [T],
+    lo: isize,
+    hi: isize,
+    compare: &mut F,
+) -> (isize, isize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // The pivot `arr[hi]` is moved to the front. During partitioning,
+    // `arr[i]` always holds an element equal to the pivot.
+    swap(arr, lo as usize, hi as usize);
+    trace::pivot(&arr[lo as usize]);
     let mut i = lo; // smaller
-    let mut j = lo; // equal
+    let mut j = lo + 1; // equal
     let mut k = hi; // large
 
     while j <= k {
-        if arr[j as usize] < pivot {
-            arr.swap(i as usize, j as usize);
-            i += 1;
-            j += 1;
-        } else if arr[j as usize] > pivot {
-            arr.swap(k as usize, j as usize);
-            k -= 1;
-        } else {
-            // No swap when identicial.
-            j += 1;
+        match compare(&arr[j as usize], &arr[i as usize]) {
+            Ordering::Less => {
+                swap(arr, i as usize, j as usize);
+                i += 1;
+                j += 1;
+            }
+            Ordering::Greater => {
+                swap(arr, k as usize, j as usize);
+                k -= 1;
+            }
+            Ordering::Equal => {
+                // No swap when identicial.
+                j += 1;
+            }
         }
     }
 
     // Return smaller and larger pointer to avoid iterate duplicate elements.
     (i, k)
 }
+// ANCHOR_END: partition_3way
+
+// -------------------------------------
+// 3-way string quicksort
+// -------------------------------------
+
+/// 3-way string quicksort, also known as multikey quicksort.
+///
+/// Keys are sorted lexicographically by bytes. Like `quicksort_3way`, keys
+/// are partitioned into less, equal and greater piles, but compared by only
+/// the byte at current depth. The equal pile then moves on to the next byte,
+/// so common prefixes are never compared again.
+///
+/// Reference: Bentley and Sedgewick, "Fast Algorithms for Sorting and
+/// Searching Strings".
+pub fn quicksort_3way_string<T: AsRef<[u8]>>(arr: &mut [T]) {
+    quicksort_helper_3way_string(arr, 0);
+}
+
+/// Recursion helper. Every key in `arr` shares the same first `depth` bytes.
+///
+/// Only the two smaller piles are sorted recursively. The largest pile is
+/// sorted by the loop, so the recursion is O(log n) deep however long the
+/// common prefixes are.
+fn quicksort_helper_3way_string<T: AsRef<[u8]>>(mut arr: &mut [T], mut depth: usize) {
+    while arr.len() >= 2 {
+        let mut compare =
+            stats::counting(move |a: &T, b: &T| byte_at(a, depth).cmp(&byte_at(b, depth)));
+        // Middle element as the pivot to avoid the worst case on sorted input.
+        let hi = arr.len() - 1;
+        swap(arr, arr.len() / 2, hi);
+        let (smaller, larger) = partition_3way(arr, 0, hi as isize, &mut compare);
+        let (smaller, larger) = (smaller as usize, larger as usize);
+
+        // Piles as `(lo, hi, depth)`, with no depth if the pile needs no
+        // sorting: keys equal at `depth` and ending here are all equal.
+        let equal_depth = byte_at(&arr[smaller], depth).map(|_| depth + 1);
+        let mut piles = [
+            (0, smaller, Some(depth)),
+            (smaller, larger + 1, equal_depth),
+            (larger + 1, arr.len(), Some(depth)),
+        ];
+        piles.sort_by_key(|&(lo, hi, _)| hi - lo);
+        for &(lo, hi, d) in &piles[..2] {
+            if let Some(d) = d {
+                quicksort_helper_3way_string(&mut arr[lo..hi], d);
+            }
+        }
+        match piles[2] {
+            (lo, hi, Some(d)) => {
+                arr = &mut arr[lo..hi];
+                depth = d;
+            }
+            _ => return,
+        }
+    }
+}
 
 // -------------------------------------
 // Hoare partition scheme
 // -------------------------------------
 
 /// Quicksort with Hoare parition scheme
-pub fn quicksort_hoare(arr: &mut [i32]) {
+pub fn quicksort_hoare<T: Ord>(arr: &mut [T]) {
+    quicksort_hoare_by(arr, T::cmp);
+}
+
+/// Quicksort with Hoare parition scheme and a comparator function.
+pub fn quicksort_hoare_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     if arr.is_empty() {
         return;
     }
     let hi = arr.len() - 1;
-    quicksort_helper_hoare(arr, 0, hi);
+    quicksort_helper_hoare(arr, 0, hi, &mut compare);
+}
+
+/// Quicksort with Hoare parition scheme and a key extraction function.
+pub fn quicksort_hoare_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quicksort_hoare_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
 /// Recursion helper
-fn quicksort_helper_hoare(arr: &mut [i32], lo: usize, hi: usize) {
+fn quicksort_helper_hoare<T, F>(arr: &mut [T], lo: usize, hi: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     if lo < hi {
-        let pivot = partition_hoare(arr, lo, hi);
-        quicksort_helper_hoare(arr, lo, pivot);
-        quicksort_helper_hoare(arr, pivot + 1, hi);
+        let pivot = partition_hoare(arr, lo, hi, compare);
+        quicksort_helper_hoare(arr, lo, pivot, compare);
+        quicksort_helper_hoare(arr, pivot + 1, hi, compare);
     }
 }
 
@@ -160,56 +421,178 @@ fn quicksort_helper_hoare(arr: &mut [i32], lo: usize, hi: usize) {
 ///
 /// Note that the return value is not necessarily be the index of the pivot,
 /// and the pivot is located somewhere of the first partition.
-fn partition_hoare(arr: &mut [i32], lo: usize, hi: usize) -> usize {
-    let pivot = arr[lo];
+// ANCHOR: partition_hoare
+pub(crate) fn partition_hoare<T, F>(arr: &mut [T], lo: usize, hi: usize, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // Track where the pivot is, since it may be swapped.
+    let mut pivot = lo;
+    trace::pivot(&arr[pivot]);
     let mut i = lo;
     let mut j = hi;
 
     loop {
         // Find element >= pivot from leftmost element.
-        while arr[i] < pivot {
+        while compare(&arr[i], &arr[pivot]) == Ordering::Less {
+            // 1
             i += 1;
         }
         // Find element <= pivot from rightmost element.
-        while arr[j] > pivot {
+        while compare(&arr[j], &arr[pivot]) == Ordering::Greater {
+            // 2
             j -= 1;
         }
         if i >= j {
             return j;
         }
         // Two elements are misplaced, swap them.
-        arr.swap(i, j);
+        swap(arr, i, j); // 3
+        if pivot == i {
+            // 4
+            pivot = j;
+        } else if pivot == j {
+            pivot = i;
+        }
         i += 1;
         j -= 1;
     }
 }
+// ANCHOR_END: partition_hoare
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(quicksort);
+    by_cases!(quicksort_by, quicksort_by_key);
 }
 
 #[cfg(test)]
 mod optimized {
     use super::*;
     base_cases!(quicksort_optimized);
+    by_cases!(quicksort_optimized_by, quicksort_optimized_by_key);
 }
 
 #[cfg(test)]
 mod manual_tco {
     use super::*;
     base_cases!(quicksort_manual_tco);
+    by_cases!(quicksort_manual_tco_by, quicksort_manual_tco_by_key);
+}
+
+#[cfg(test)]
+mod parallel_base {
+    use super::*;
+
+    const CONFIG: ParallelConfig = ParallelConfig {
+        workers: 4,
+        cutoff: 2,
+    };
+
+    fn parallel_quicksort_<T: Ord + Send>(arr: &mut [T]) {
+        parallel_quicksort(arr, CONFIG);
+    }
+
+    fn parallel_quicksort_by_<T: Send>(arr: &mut [T], compare: impl Fn(&T, &T) -> Ordering + Sync) {
+        parallel_quicksort_by(arr, CONFIG, compare);
+    }
+
+    fn parallel_quicksort_by_key_<T: Send, K: Ord>(arr: &mut [T], key: impl Fn(&T) -> K + Sync) {
+        parallel_quicksort_by_key(arr, CONFIG, key);
+    }
+
+    base_cases!(parallel_quicksort_);
+    by_cases!(parallel_quicksort_by_, parallel_quicksort_by_key_);
+
+    #[test]
+    fn same_as_sequential() {
+        // Equal keys carry their original positions, which end up in the
+        // same unstable order.
+        for &workers in &[1, 2, 3, 8] {
+            for &cutoff in &[2, 1000] {
+                let config = ParallelConfig { workers, cutoff };
+                let arr: Vec<_> = (0..10_000)
+                    .map(|i| (rand::random::<i32>() % 100, i))
+                    .collect();
+                let mut sequential = arr.clone();
+                quicksort_by_key(&mut sequential, |t| t.0);
+                let mut parallel = arr;
+                parallel_quicksort_by_key(&mut parallel, config, |t| t.0);
+                assert_eq!(parallel, sequential);
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_and_reversed() {
+        for arr in [(0..3000).collect::<Vec<_>>(), (0..3000).rev().collect()] {
+            let mut res = arr.clone();
+            res.sort();
+            let mut arr = arr;
+            parallel_quicksort(&mut arr, ParallelConfig::default());
+            assert_eq!(arr, res);
+        }
+    }
 }
 
 #[cfg(test)]
 mod three_way {
     use super::*;
     base_cases!(quicksort_3way);
+    by_cases!(quicksort_3way_by, quicksort_3way_by_key);
+}
+
+#[cfg(test)]
+mod three_way_string {
+    use super::*;
+    base_cases!(quicksort_3way_string, String);
+
+    fn assert_sorted<T: AsRef<[u8]> + Ord + Clone + std::fmt::Debug>(mut arr: Vec<T>) {
+        let mut res = arr.clone();
+        res.sort();
+        quicksort_3way_string(&mut arr);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_sorted(vec!["ab", "a", "", "abc", "b", "ab", "a", ""]);
+    }
+
+    #[test]
+    fn common_prefix() {
+        let arr: Vec<_> = (0..2000)
+            .map(|_| format!("identifier_{}", rand::random::<u16>()))
+            .collect();
+        assert_sorted(arr);
+    }
+
+    #[test]
+    fn long_common_prefix() {
+        // Recursing once per shared byte would overflow the stack.
+        let prefix = "x".repeat(100_000);
+        let mut arr: Vec<_> = (0..20).map(|i| format!("{}{}", prefix, i % 7)).collect();
+        arr.extend(vec![prefix.clone(); 20]);
+        assert_sorted(arr);
+        assert_sorted(vec![prefix.clone(), prefix]);
+    }
+
+    #[test]
+    fn random_bytes() {
+        let arr: Vec<Vec<u8>> = (0..2000)
+            .map(|_| {
+                let len = rand::random::<usize>() % 8;
+                (0..len).map(|_| rand::random::<u8>() % 4).collect()
+            })
+            .collect();
+        assert_sorted(arr);
+    }
 }
 
 #[cfg(test)]
 mod hoare {
     use super::*;
     base_cases!(quicksort_hoare);
+    by_cases!(quicksort_hoare_by, quicksort_hoare_by_key);
 }
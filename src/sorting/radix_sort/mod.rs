@@ -1,28 +1,333 @@
-use crate::sorting::counting_sort;
+use crate::sorting::counting_sort::MAX_KEY_RANGE;
+use crate::sorting::stats::{self, swap};
+use crate::sorting::{insertion_sort_by, trace};
 
-/// Radix sort for sorting unsigned integers.
+/// Buckets shorter than this are sorted by insertion sort in MSD radix sort.
+const INSERTION_THRESHOLD: usize = 16;
+
+/// Integers which can be sorted by radix sort.
+pub trait RadixKey: Copy {
+    /// Number of bits of the integer type.
+    const BITS: u32;
+
+    /// Map to an unsigned key in the same order.
+    ///
+    /// Signed integers flip their sign bit, so negative numbers come before
+    /// non-negative ones, e.g. `i8::MIN` to `0` and `-1` to `127`.
+    fn radix_key(self) -> u64;
+}
+
+macro_rules! impl_radix_key_unsigned {
+    ($($ty:ty),+) => {
+        $(impl RadixKey for $ty {
+            const BITS: u32 = <$ty>::BITS;
+
+            fn radix_key(self) -> u64 {
+                self as u64
+            }
+        })+
+    };
+}
+
+macro_rules! impl_radix_key_signed {
+    ($($ty:ty => $unsigned:ty),+) => {
+        $(impl RadixKey for $ty {
+            const BITS: u32 = <$ty>::BITS;
+
+            fn radix_key(self) -> u64 {
+                (self as $unsigned ^ 1 << (<$ty>::BITS - 1)) as u64
+            }
+        })+
+    };
+}
+
+impl_radix_key_unsigned!(u8, u16, u32, u64, usize);
+impl_radix_key_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+/// Radix sort for sorting integers, with 256 as the radix.
+///
+/// * `arr` - Collection of value to be sorted in place.
+pub fn radix_sort<T: RadixKey>(arr: &mut [T]) {
+    radix_sort_with(arr, 256);
+}
+
+/// Radix sort for sorting integers with a given radix.
 ///
 /// * `arr` - Collection of value to be sorted in place.
-pub fn radix_sort(arr: &mut [i32]) {
-    // 1. Choose 10 as our radix.
-    let radix = 10;
+/// * `radix` - Number of possible values of a digit. Must be a power of two,
+///   so a digit is extracted by bit shifting and masking.
+///
+/// # Panics
+///
+/// Panics if `radix` is not a power of two from 2 to 2^24, the key range
+/// limit of counting sort.
+// ANCHOR: radix_sort_with
+pub fn radix_sort_with<T: RadixKey>(arr: &mut [T], radix: usize) {
+    assert!(
+        radix >= 2 && radix as u64 <= MAX_KEY_RANGE && radix.is_power_of_two(),
+        "radix must be a power of two from 2 to 2^24"
+    );
+    // 1. Bits of a digit, e.g. 8 bits for a radix of 256.
+    let bits = radix.trailing_zeros();
+    let mask = radix as u64 - 1;
     // 2. Started from least significant digit (rightmost).
-    let mut digit = 1;
-    // 3. Find the maximum value to determine break point of the loop.
-    let max_value = arr // 3
-        .iter()
-        .max()
-        .unwrap_or(&0)
-        .clone();
-    // 4. Sorting subroutine (use counting sort).
-    while digit <= max_value {
-        counting_sort(arr, 0, 9, |t| (t / digit % radix) as usize);
-        digit *= radix;
+    let mut shift = 0;
+    // 3. Find the maximum key to determine break point of the loop.
+    let max_key = arr.iter().map(|t| t.radix_key()).max().unwrap_or(0);
+    // 4. Sorting subroutine (use counting sort). Elements are sorted back
+    // and forth between `arr` and a buffer. Both the buffer and the count
+    // array are allocated once and reused by every pass.
+    let mut buf = arr.to_vec();
+    let mut counts = vec![0; radix];
+    stats::record_allocation();
+    stats::record_allocation();
+    let mut in_buf = false;
+    while shift < T::BITS && max_key >> shift > 0 {
+        if in_buf {
+            counting_pass(&buf, arr, &mut counts, shift, mask);
+        } else {
+            counting_pass(arr, &mut buf, &mut counts, shift, mask);
+        }
+        in_buf = !in_buf;
+        shift += bits;
+    }
+    if in_buf {
+        arr.copy_from_slice(&buf);
+        stats::record_moves(arr.len());
+        trace::writes(arr);
+    }
+}
+
+/// Counting sort from `src` into `dst` by the digit `key >> shift & mask`.
+///
+/// * `counts` - Count array with one slot for each digit value.
+fn counting_pass<T: RadixKey>(
+    src: &[T],
+    dst: &mut [T],
+    counts: &mut [usize],
+    shift: u32,
+    mask: u64,
+) {
+    let digit = |t: T| (t.radix_key() >> shift & mask) as usize;
+
+    // 1. Count elements of each digit.
+    counts.iter_mut().for_each(|count| *count = 0);
+    for &t in src {
+        counts[digit(t)] += 1;
+    }
+
+    // 2. Calculate prefix sum, the start position of each digit.
+    let mut sum = 0;
+    for count in counts.iter_mut() {
+        let n = *count;
+        *count = sum;
+        sum += n;
+    }
+
+    // 3. Place elements in order. Elements with the same digit keep their
+    // relative order, which later passes rely on.
+    for &t in src {
+        let d = digit(t);
+        dst[counts[d]] = t;
+        counts[d] += 1;
+    }
+    stats::record_moves(src.len());
+    trace::writes(dst);
+}
+// ANCHOR_END: radix_sort_with
+
+/// Byte of a key at `depth`. `None` if the key is shorter, which orders
+/// before any byte, so a prefix comes before longer keys.
+pub(crate) fn byte_at<T: AsRef<[u8]>>(key: &T, depth: usize) -> Option<u8> {
+    key.as_ref().get(depth).copied()
+}
+
+/// MSD radix sort for strings and byte slices.
+///
+/// Keys are sorted lexicographically by bytes. Elements are distributed into
+/// 257 buckets by the byte at current depth, one for keys ending here and one
+/// for each byte value, then each bucket is sorted recursively by the next
+/// byte. Small buckets are sorted by insertion sort.
+///
+/// Elements are moved between buckets in place by swapping, known as
+/// American flag sort, so no auxiliary array of elements is needed.
+///
+/// * `arr` - Collection of value to be sorted in place.
+pub fn msd_radix_sort<T: AsRef<[u8]>>(arr: &mut [T]) {
+    msd_radix_sort_helper(arr, 0);
+}
+
+/// Recursion helper. Every key in `arr` shares the same first `depth` bytes.
+///
+/// Only buckets smaller than the largest one are sorted recursively. The
+/// largest bucket is sorted by the loop at the next depth, so the recursion
+/// is O(log n) deep however long the common prefixes are.
+fn msd_radix_sort_helper<T: AsRef<[u8]>>(mut arr: &mut [T], mut depth: usize) {
+    loop {
+        if arr.len() <= INSERTION_THRESHOLD {
+            insertion_sort_by(arr, |a, b| a.as_ref()[depth..].cmp(&b.as_ref()[depth..]));
+            return;
+        }
+
+        // Bucket 0 for keys ending at `depth`, others for bytes shifted by one.
+        let bucket = |key: &T| byte_at(key, depth).map_or(0, |b| b as usize + 1);
+
+        // 1. Count elements of each bucket, and find where each bucket starts.
+        let mut starts = [0; 258];
+        for key in arr.iter() {
+            starts[bucket(key) + 1] += 1;
+        }
+        for b in 1..starts.len() {
+            starts[b] += starts[b - 1];
+        }
+
+        // 2. Swap every element into its bucket. `next[b]` is the first
+        // position of bucket `b` not yet holding an element of the bucket.
+        let mut next = starts;
+        for b in 0..257 {
+            while next[b] < starts[b + 1] {
+                let target = bucket(&arr[next[b]]);
+                if target == b {
+                    next[b] += 1;
+                } else {
+                    swap(arr, next[b], next[target]);
+                    next[target] += 1;
+                }
+            }
+        }
+
+        // 3. Sort each bucket by the next byte. Keys in bucket 0 are all
+        // equal.
+        let largest = (1..257).max_by_key(|&b| starts[b + 1] - starts[b]).unwrap();
+        for b in (1..257).filter(|&b| b != largest) {
+            msd_radix_sort_helper(&mut arr[starts[b]..starts[b + 1]], depth + 1);
+        }
+        arr = &mut arr[starts[largest]..starts[largest + 1]];
+        depth += 1;
     }
 }
 
 #[cfg(test)]
 mod base {
     use super::*;
-    base_cases!(radix_sort);
+    base_cases!(radix_sort, i32);
+}
+
+#[cfg(test)]
+mod msd {
+    use super::*;
+    base_cases!(msd_radix_sort, String);
+
+    fn assert_sorted<T: AsRef<[u8]> + Ord + Clone + std::fmt::Debug>(mut arr: Vec<T>) {
+        let mut res = arr.clone();
+        res.sort();
+        msd_radix_sort(&mut arr);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_sorted(vec!["ab", "a", "", "abc", "b", "ab", "a", ""]);
+    }
+
+    #[test]
+    fn long_common_prefix() {
+        // Recursing once per shared byte would overflow the stack.
+        let prefix = "x".repeat(10_000);
+        let mut arr: Vec<_> = (0..20).map(|i| format!("{}{}", prefix, i % 7)).collect();
+        arr.extend(vec![prefix.clone(); 20]);
+        assert_sorted(arr);
+    }
+
+    #[test]
+    fn paths() {
+        let arr: Vec<_> = (0..2000)
+            .map(|_| {
+                let depth = rand::random::<usize>() % 4;
+                (0..=depth)
+                    .map(|_| {
+                        ["src", "sorting", "lib.rs", "mod.rs", "a"][rand::random::<usize>() % 5]
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+        assert_sorted(arr);
+    }
+
+    #[test]
+    fn random_bytes() {
+        let arr: Vec<Vec<u8>> = (0..2000)
+            .map(|_| {
+                let len = rand::random::<usize>() % 8;
+                (0..len).map(|_| rand::random()).collect()
+            })
+            .collect();
+        assert_sorted(arr);
+    }
+}
+
+#[cfg(test)]
+mod integers {
+    use super::*;
+    use crate::sorting::test_cases::allocations;
+    use crate::sorting::SortStats;
+
+    fn assert_sorted<T: RadixKey + Ord + std::fmt::Debug>(mut arr: Vec<T>, radix: usize) {
+        let mut res = arr.clone();
+        res.sort();
+        radix_sort_with(&mut arr, radix);
+        assert_eq!(arr, res);
+    }
+
+    macro_rules! random_cases {
+        ($($name:ident: $ty:ty),+) => {
+            $(#[test]
+            fn $name() {
+                for &radix in &[2, 16, 256, 1 << 16] {
+                    let mut arr: Vec<$ty> = (0..1000).map(|_| rand::random()).collect();
+                    arr.extend_from_slice(&[<$ty>::MIN, <$ty>::MAX, 0, 1]);
+                    assert_sorted(arr, radix);
+                }
+            })+
+        };
+    }
+
+    random_cases!(
+        i8: i8, i16: i16, i32: i32, i64: i64, isize: isize,
+        u8: u8, u16: u16, u32: u32, u64: u64, usize: usize
+    );
+
+    #[test]
+    fn negative() {
+        assert_sorted(vec![-3, 170, -45, 75, i32::MIN, -90, 802, i32::MAX, -2], 16);
+    }
+
+    #[test]
+    fn allocates_once() {
+        // One buffer and one count array, however many passes.
+        let mut arr: Vec<i32> = (0..1000).map(|_| rand::random()).collect();
+        let mut stats = SortStats::default();
+        let count = allocations(|| stats = SortStats::collect(|| radix_sort_with(&mut arr, 2)));
+        assert_eq!(count, 2);
+        assert_eq!(stats.allocations, 2);
+        assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn small_values_of_wide_type() {
+        assert_sorted(vec![3u64, 1, 2], 256);
+    }
+
+    #[test]
+    #[should_panic(expected = "radix must be a power of two")]
+    fn radix_not_power_of_two() {
+        radix_sort_with(&mut [3, 2, 1], 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "radix must be a power of two from 2 to 2^24")]
+    fn radix_too_large() {
+        radix_sort_with(&mut [3u64, 2, 1], 1 << 32);
+    }
 }
@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+
+use crate::sorting::insertion_sort_by;
+use crate::sorting::introsort::median_of_three;
+use crate::sorting::quicksort::{partition_3way, partition_hoare};
+use crate::sorting::stats::{self, swap};
+
+/// Quickselect.
+///
+/// Find the `k`-th smallest element (zero-based) in expected O(n) time,
+/// by partitioning like quicksort but only continuing on the side containing
+/// position `k`. The pivot is the median of three, partitioned with Hoare
+/// partition scheme.
+///
+/// The slice is reordered as `nth_element` does.
+///
+/// # Panics
+///
+/// Panics if `k` is out of bounds.
+pub fn quickselect<T: Ord>(arr: &mut [T], k: usize) -> &T {
+    quickselect_by(arr, k, T::cmp)
+}
+
+/// Quickselect with a comparator function.
+pub fn quickselect_by<T, F>(arr: &mut [T], k: usize, compare: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(k < arr.len(), "k out of bounds");
+    let mut compare = stats::counting(compare);
+    quickselect_helper(arr, k, false, &mut compare);
+    &arr[k]
+}
+
+/// Quickselect with a key extraction function.
+pub fn quickselect_by_key<T, K, F>(arr: &mut [T], k: usize, mut key: F) -> &T
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    quickselect_by(arr, k, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Selection by median of medians.
+///
+/// Find the `k`-th smallest element (zero-based) in worst-case O(n) time.
+/// The pivot is the median of medians of groups of five elements, which is
+/// guaranteed to be greater than and less than 30% of elements.
+///
+/// The slice is reordered as `nth_element` does.
+///
+/// Reference: Blum, Floyd, Pratt, Rivest and Tarjan, "Time bounds for
+/// selection".
+///
+/// # Panics
+///
+/// Panics if `k` is out of bounds.
+pub fn select<T: Ord>(arr: &mut [T], k: usize) -> &T {
+    select_by(arr, k, T::cmp)
+}
+
+/// Selection by median of medians with a comparator function.
+pub fn select_by<T, F>(arr: &mut [T], k: usize, compare: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(k < arr.len(), "k out of bounds");
+    let mut compare = stats::counting(compare);
+    select_helper(arr, k, &mut compare);
+    &arr[k]
+}
+
+/// Selection by median of medians with a key extraction function.
+pub fn select_by_key<T, K, F>(arr: &mut [T], k: usize, mut key: F) -> &T
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    select_by(arr, k, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Rearrange elements like C++ `std::nth_element`.
+///
+/// Afterwards, `arr[k]` is the element which would be there if sorted, no
+/// element in `arr[..k]` is greater than it, and no element in `arr[k + 1..]`
+/// is less than it.
+///
+/// Runs quickselect, and switches to median of medians once partitions stop
+/// shrinking the range fast enough, so it is O(n) in the worst case.
+///
+/// # Panics
+///
+/// Panics if `k` is out of bounds.
+pub fn nth_element<T: Ord>(arr: &mut [T], k: usize) {
+    nth_element_by(arr, k, T::cmp);
+}
+
+/// Rearrange elements like C++ `std::nth_element` with a comparator
+/// function.
+pub fn nth_element_by<T, F>(arr: &mut [T], k: usize, compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(k < arr.len(), "k out of bounds");
+    let mut compare = stats::counting(compare);
+    quickselect_helper(arr, k, true, &mut compare);
+}
+
+/// Rearrange elements like C++ `std::nth_element` with a key extraction
+/// function.
+pub fn nth_element_by_key<T, K, F>(arr: &mut [T], k: usize, mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    nth_element_by(arr, k, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Quickselect loop. With `fallback`, switches to median of medians when
+/// two partitions in a row leave more than half of the range.
+// ANCHOR: quickselect_helper
+fn quickselect_helper<T, F>(arr: &mut [T], k: usize, fallback: bool, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut lo = 0;
+    let mut hi = arr.len() - 1;
+    // Length of the range two partitions ago.
+    let mut checkpoint = arr.len();
+    let mut partitions = 0;
+    while lo < hi {
+        if partitions == 2 {
+            if fallback && hi - lo + 1 > checkpoint / 2 {
+                select_helper(&mut arr[lo..=hi], k - lo, compare);
+                return;
+            }
+            checkpoint = hi - lo + 1;
+            partitions = 0;
+        }
+        partitions += 1;
+
+        // Move the median of three to the front as the pivot.
+        let mid = lo + (hi - lo) / 2;
+        if hi - lo >= 2 {
+            median_of_three(arr, lo, mid, hi, compare);
+            swap(arr, lo, mid);
+        }
+
+        // Elements in `arr[lo..=j]` are no greater than those in
+        // `arr[j + 1..=hi]`. Continue on the side containing `k`.
+        let j = partition_hoare(arr, lo, hi, compare);
+        if k <= j {
+            hi = j;
+        } else {
+            lo = j + 1;
+        }
+    }
+}
+// ANCHOR_END: quickselect_helper
+
+/// Median of medians recursion helper.
+// ANCHOR: select_helper
+fn select_helper<T, F>(mut arr: &mut [T], mut k: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let len = arr.len();
+        if len <= 5 {
+            insertion_sort_by(arr, &mut *compare);
+            return;
+        }
+
+        // 1. Sort each group of five, and move its median to the front.
+        let groups = len.div_ceil(5);
+        for g in 0..groups {
+            let lo = g * 5;
+            let hi = (lo + 5).min(len);
+            insertion_sort_by(&mut arr[lo..hi], &mut *compare);
+            swap(arr, g, lo + (hi - lo - 1) / 2);
+        }
+
+        // 2. Find the median of medians recursively.
+        select_helper(&mut arr[..groups], groups / 2, compare);
+
+        // 3. Partition around it. Elements equal to the pivot are grouped
+        // in the middle, so duplicates never slow it down.
+        swap(arr, groups / 2, len - 1);
+        let (smaller, larger) = partition_3way(arr, 0, len as isize - 1, compare);
+        let (smaller, larger) = (smaller as usize, larger as usize);
+        if k < smaller {
+            arr = &mut arr[..smaller];
+        } else if k > larger {
+            arr = &mut arr[larger + 1..];
+            k -= larger + 1;
+        } else {
+            return;
+        }
+    }
+}
+// ANCHOR_END: select_helper
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::{antiqsort, SortStats};
+
+    type Select = fn(&mut [i32], usize);
+
+    const ALGOS: [(&str, Select); 3] = [
+        ("quickselect", |arr, k| {
+            quickselect(arr, k);
+        }),
+        ("select", |arr, k| {
+            select(arr, k);
+        }),
+        ("nth_element", nth_element),
+    ];
+
+    /// Check `arr` is partitioned around position `k`.
+    fn assert_nth(arr: &[i32], k: usize, res: &[i32], name: &str) {
+        assert_eq!(arr[k], res[k], "{} k = {}", name, k);
+        assert!(arr[..k].iter().all(|v| *v <= arr[k]), "{} k = {}", name, k);
+        assert!(
+            arr[k + 1..].iter().all(|v| *v >= arr[k]),
+            "{} k = {}",
+            name,
+            k
+        );
+    }
+
+    fn assert_every_k(arr: &[i32]) {
+        let mut res = arr.to_vec();
+        res.sort();
+        for (name, algo) in ALGOS.iter() {
+            for k in 0..arr.len() {
+                let mut arr = arr.to_vec();
+                algo(&mut arr, k);
+                assert_nth(&arr, k, &res, name);
+            }
+        }
+    }
+
+    #[test]
+    fn small() {
+        assert_every_k(&[1]);
+        assert_every_k(&[2, 1]);
+        assert_every_k(&[3, 1, 2]);
+        assert_every_k(&[1, 5, 3, 3, 4, 1, 3, 4]);
+    }
+
+    #[test]
+    fn random() {
+        let arr: Vec<_> = (0..200).map(|_| rand::random::<i32>() % 50).collect();
+        assert_every_k(&arr);
+    }
+
+    #[test]
+    fn patterns() {
+        assert_every_k(&(0..100).collect::<Vec<_>>());
+        assert_every_k(&(0..100).rev().collect::<Vec<_>>());
+        assert_every_k(&[7; 100]);
+        assert_every_k(&(0..100).map(|i| i % 7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn returns_kth() {
+        let mut arr = [5, 1, 4, 2, 3];
+        assert_eq!(*quickselect(&mut arr, 1), 2);
+        assert_eq!(*select(&mut arr, 3), 4);
+        assert_eq!(*quickselect_by(&mut arr, 0, |a, b| b.cmp(a)), 5);
+        assert_eq!(*select_by_key(&mut arr, 0, |v: &i32| (v - 3).abs()), 3);
+        nth_element_by_key(&mut arr, 4, |v| -v);
+        assert_eq!(arr[4], 1);
+    }
+
+    #[test]
+    fn linear_worst_case() {
+        // Median of medians, also as the fallback of `nth_element`, makes a
+        // linear number of comparisons even on adversarial inputs.
+        let n = 10_000;
+        let inputs: Vec<Vec<i32>> = vec![
+            (0..n).collect(),
+            (0..n).rev().collect(),
+            vec![0; n as usize],
+            (0..n).map(|i| i % 2).collect(),
+        ];
+        for arr in inputs {
+            for &k in &[0, n as usize / 2, n as usize - 1] {
+                let mut a = arr.clone();
+                let stats = SortStats::collect(|| {
+                    select(&mut a, k);
+                });
+                assert!(stats.comparisons < 20 * n as usize);
+                let mut a = arr.clone();
+                let stats = SortStats::collect(|| nth_element(&mut a, k));
+                assert!(stats.comparisons < 20 * n as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn antiqsort_linear() {
+        // Median-of-three quickselect takes Θ(n^2) comparisons on its own
+        // adversary. `nth_element` gives up on quickselect early enough to
+        // stay linear, also against an adversary built for itself.
+        let n = 10_000;
+        for &k in &[0, n / 2, n - 1] {
+            let arr = antiqsort(n, |a, c| {
+                quickselect_by(a, k, c);
+            });
+            if k > 0 {
+                let stats = SortStats::collect(|| {
+                    quickselect(&mut arr.clone(), k);
+                });
+                assert!(stats.comparisons >= n * n / 8, "k = {}", k);
+            }
+            let against_self = antiqsort(n, |a, c| nth_element_by(a, k, c));
+            for mut arr in [arr, against_self] {
+                let stats = SortStats::collect(|| nth_element(&mut arr, k));
+                assert!(stats.comparisons < 20 * n, "k = {}: {:?}", k, stats);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "k out of bounds")]
+    fn out_of_bounds() {
+        nth_element(&mut [1, 2, 3], 3);
+    }
+}
@@ -1,16 +1,150 @@
+use std::cmp::Ordering;
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::slice;
+
+use crate::sorting::stats::{self, swap};
+use crate::sorting::trace;
+
 /// Selection sort.
-pub fn selection_sort(arr: &mut [i32]) {
+pub fn selection_sort<T: Ord>(arr: &mut [T]) {
+    selection_sort_by(arr, T::cmp);
+}
+
+/// Selection sort with a comparator function.
+// ANCHOR: selection_sort
+pub fn selection_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let len = arr.len();
     // Rust would skip iteration if lower bound >= upper bound.
     // Hence, no need to `len - 1`.
     for i in 0..len {
+        // 1
         let mut temp = i;
         for j in (i + 1)..len {
-            if arr[temp] > arr[j] {
+            // 2
+            if compare(&arr[temp], &arr[j]) == Ordering::Greater {
                 temp = j;
             }
         }
-        arr.swap(i, temp);
+        swap(arr, i, temp); // 3
+    }
+}
+// ANCHOR_END: selection_sort
+
+/// Selection sort with a key extraction function.
+pub fn selection_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    selection_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Cycle sort.
+///
+/// Every permutation is made of cycles. For each position, count elements
+/// less than its element to find where the element belongs, write it there,
+/// pick up the element it displaces, and repeat until the cycle returns to
+/// the starting position.
+///
+/// Each element is written once, straight to its final position, so there
+/// are at most n writes to the slice, and none to elements already in place.
+/// This makes it suitable for storage where writes are expensive, at the
+/// cost of O(n^2) comparisons. Not stable.
+///
+/// Reference: B. K. Haddon, "Cycle-sort: a linear sorting method".
+pub fn cycle_sort<T: Ord>(arr: &mut [T]) {
+    cycle_sort_by(arr, T::cmp);
+}
+
+/// Cycle sort with a comparator function.
+pub fn cycle_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    let len = arr.len();
+    let v = arr.as_mut_ptr();
+    for start in 0..len.saturating_sub(1) {
+        // Safety: elements are only accessed via `v` within bounds. The
+        // element taken out of `start` is held by `hole`, which writes what
+        // it holds back into `start` when dropped, even if `compare` panics.
+        // Elements written into the slice are swapped out of `hole`, so
+        // every element is owned exactly once.
+        unsafe {
+            let mut pos = cycle_position(v, start, len, &*v.add(start), &mut compare);
+            if pos == start {
+                continue;
+            }
+            let mut hole = CycleHole {
+                item: ManuallyDrop::new(ptr::read(v.add(start))),
+                dest: v.add(start),
+            };
+            stats::record_moves(1);
+            while pos != start {
+                // Skip elements equal to the held one, which are in place.
+                while compare(&*v.add(pos), &hole.item) == Ordering::Equal {
+                    pos += 1;
+                }
+                ptr::swap(&mut *hole.item, v.add(pos));
+                stats::record_moves(1);
+                trace::writes(slice::from_raw_parts(v.add(pos), 1));
+                pos = cycle_position(v, start, len, &hole.item, &mut compare);
+            }
+            // Close the cycle at `start`.
+            drop(hole);
+            stats::record_moves(1);
+            trace::writes(slice::from_raw_parts(v.add(start), 1));
+        }
+    }
+}
+
+/// Final position of `item` in a cycle starting at `start`: `start` plus the
+/// number of elements after `start` less than it.
+///
+/// Safety: `v` must point to `len` valid elements.
+unsafe fn cycle_position<T, F>(
+    v: *const T,
+    start: usize,
+    len: usize,
+    item: &T,
+    compare: &mut F,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let smaller = (start + 1..len)
+        .filter(|&i| compare(&*v.add(i), item) == Ordering::Less)
+        .count();
+    start + smaller
+}
+
+/// Cycle sort with a key extraction function.
+pub fn cycle_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    cycle_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// An element taken out of the slice.
+///
+/// When dropped, writes the element to `dest`.
+struct CycleHole<T> {
+    item: ManuallyDrop<T>,
+    dest: *mut T,
+}
+
+impl<T> Drop for CycleHole<T> {
+    fn drop(&mut self) {
+        // Safety: `dest` is the position the element was taken from, whose
+        // value has been moved out.
+        unsafe { ptr::copy_nonoverlapping(&*self.item, self.dest, 1) }
     }
 }
 
@@ -18,4 +152,82 @@ pub fn selection_sort(arr: &mut [i32]) {
 mod base {
     use super::*;
     base_cases!(selection_sort);
+    by_cases!(selection_sort_by, selection_sort_by_key);
+}
+
+#[cfg(test)]
+mod cycle {
+    use super::*;
+    use crate::sorting::{count_writes, insertion_sort, Pattern, SortEvent, Trace};
+
+    base_cases!(cycle_sort);
+    by_cases!(cycle_sort_by, cycle_sort_by_key);
+
+    #[test]
+    fn writes_only_misplaced_once() {
+        for pattern in Pattern::ALL.iter() {
+            for &len in &[2, 10, 500] {
+                let mut arr = pattern.generate(len, 6);
+                let mut res = arr.clone();
+                res.sort();
+                let trace = Trace::record(&mut arr, cycle_sort);
+                assert_eq!(arr, res);
+
+                let mut written = vec![false; len];
+                for event in trace.events() {
+                    match *event {
+                        SortEvent::Write(i, v) => {
+                            assert_ne!(trace.initial()[i], res[i], "{:?} in place", pattern);
+                            assert_eq!(v, res[i], "{:?} not final", pattern);
+                            assert!(!written[i], "{:?} written twice", pattern);
+                            written[i] = true;
+                        }
+                        SortEvent::Swap(..) => panic!("cycle sort never swaps"),
+                        _ => {}
+                    }
+                }
+                let misplaced = (0..len).filter(|&i| trace.initial()[i] != res[i]);
+                assert_eq!(written.iter().filter(|&&w| w).count(), misplaced.count());
+            }
+        }
+    }
+
+    #[test]
+    fn fewest_writes() {
+        for pattern in Pattern::ALL.iter() {
+            let arr = pattern.generate(300, 7);
+            let cycle = count_writes(&mut arr.clone(), cycle_sort);
+            let selection = count_writes(&mut arr.clone(), selection_sort);
+            let insertion = count_writes(&mut arr.clone(), insertion_sort);
+            assert!(cycle <= arr.len(), "{:?}", pattern);
+            assert!(cycle <= selection, "{:?}", pattern);
+            assert!(cycle <= insertion, "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn panic_safety() {
+        use std::panic::{self, AssertUnwindSafe};
+        use std::rc::Rc;
+
+        // Every element is still owned exactly once after a panic.
+        let rcs: Vec<_> = (0..20).rev().map(Rc::new).collect();
+        let mut arr = rcs.clone();
+        let mut count = 0;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            cycle_sort_by(&mut arr, |a, b| {
+                count += 1;
+                if count == 50 {
+                    panic!("comparator panicked");
+                }
+                a.cmp(b)
+            })
+        }));
+        assert!(result.is_err());
+        let mut values: Vec<i32> = arr.iter().map(|v| **v).collect();
+        values.sort();
+        assert_eq!(values, (0..20).collect::<Vec<_>>());
+        drop(arr);
+        assert!(rcs.iter().all(|v| Rc::strong_count(v) == 1));
+    }
 }
@@ -1,24 +1,281 @@
+use std::cmp::Ordering;
+
+use crate::sorting::stats::{self, swap};
+
 /// Marcin Ciura's gap sequence.
 pub const MARCIN_GAPS: [usize; 8] = [701, 301, 132, 57, 23, 10, 4, 1];
 
-/// Shellsort
-pub fn shellsort(arr: &mut [i32]) {
+/// A gap sequence of shellsort.
+///
+/// Implement it for custom sequences. A slice of gaps, such as
+/// `MARCIN_GAPS`, is also a gap sequence.
+pub trait GapSequence {
+    /// Gaps to sort `len` elements, in descending order and ending with 1.
+    fn gaps(&self, len: usize) -> Vec<usize>;
+}
+
+/// Gaps in descending order from an ascending generator, keeping only those
+/// less than `len`.
+fn gaps_below(len: usize, ascending: impl Iterator<Item = usize>) -> Vec<usize> {
+    let mut gaps: Vec<_> = ascending.take_while(|&gap| gap < len).collect();
+    gaps.reverse();
+    gaps
+}
+
+/// Shell's original sequence: n/2, n/4, ..., 1. O(n²) in the worst case.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shell;
+
+impl GapSequence for Shell {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let halves = std::iter::successors(Some(len / 2), |&gap| Some(gap / 2));
+        halves.take_while(|&gap| gap > 0).collect()
+    }
+}
+
+/// Knuth's sequence (3^k - 1) / 2: 1, 4, 13, 40, ..., up to n/3.
+/// O(n^(3/2)) in the worst case.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Knuth;
+
+impl GapSequence for Knuth {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let ascending =
+            std::iter::successors(Some(1usize), |&gap| gap.checked_mul(3)?.checked_add(1));
+        gaps_below(len / 3 + 1, ascending)
+    }
+}
+
+/// Sedgewick's sequence 4^k + 3 * 2^(k-1) + 1, prefixed with 1: 1, 8, 23,
+/// 77, 281, .... O(n^(4/3)) in the worst case.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sedgewick;
+
+impl GapSequence for Sedgewick {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let ascending = (1..usize::BITS / 2).map(|k| (1 << (2 * k)) + 3 * (1 << (k - 1)) + 1);
+        gaps_below(len, std::iter::once(1).chain(ascending))
+    }
+}
+
+/// Tokuda's sequence ceil(h_k), where h_k = 2.25 * h_(k-1) + 1 and h_1 = 1:
+/// 1, 4, 9, 20, 46, 103, ....
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tokuda;
+
+impl GapSequence for Tokuda {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let ascending = std::iter::successors(Some(1.0f64), |h| Some(2.25 * h + 1.0))
+            .map(|h| h.ceil() as usize);
+        gaps_below(len, ascending)
+    }
+}
+
+/// Ciura's sequence, extended beyond 701 by multiplying by 2.25:
+/// 1, 4, 10, 23, 57, 132, 301, 701, 1577, ....
+///
+/// The best known sequence on average, found empirically.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ciura;
+
+impl GapSequence for Ciura {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let extended = std::iter::successors(Some(MARCIN_GAPS[0]), |&gap| {
+            gap.checked_mul(9).map(|g| g / 4)
+        })
+        .skip(1);
+        let ascending = MARCIN_GAPS.iter().rev().copied().chain(extended);
+        gaps_below(len, ascending)
+    }
+}
+
+/// Gaps given in descending order. Those not less than the length are
+/// skipped, and 1 is appended if missing, so the last pass is a plain
+/// insertion sort.
+impl GapSequence for [usize] {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        let mut gaps: Vec<_> = self
+            .iter()
+            .copied()
+            .filter(|&gap| gap > 0 && gap < len)
+            .collect();
+        if len > 1 && gaps.last() != Some(&1) {
+            gaps.push(1);
+        }
+        gaps
+    }
+}
+
+impl<const N: usize> GapSequence for [usize; N] {
+    fn gaps(&self, len: usize) -> Vec<usize> {
+        self[..].gaps(len)
+    }
+}
+
+/// Shellsort, with Marcin Ciura's gap sequence `MARCIN_GAPS`. For long
+/// arrays, [`Ciura`] extends it beyond 701 with [`shellsort_with`].
+pub fn shellsort<T: Ord>(arr: &mut [T]) {
+    shellsort_by(arr, T::cmp);
+}
+
+/// Shellsort with a comparator function.
+pub fn shellsort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    shellsort_with_by(arr, &MARCIN_GAPS, compare);
+}
+
+/// Shellsort with a key extraction function.
+pub fn shellsort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    shellsort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Shellsort with a given gap sequence.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{shellsort_with, Tokuda};
+///
+/// let mut arr = [5, 3, 8, 1, 9, 2];
+/// shellsort_with(&mut arr, &Tokuda);
+/// assert_eq!(arr, [1, 2, 3, 5, 8, 9]);
+/// ```
+pub fn shellsort_with<T, G>(arr: &mut [T], gaps: &G)
+where
+    T: Ord,
+    G: GapSequence + ?Sized,
+{
+    shellsort_with_by(arr, gaps, T::cmp);
+}
+
+/// Shellsort with a given gap sequence and a comparator function.
+///
+/// # Panics
+///
+/// Panics if the gaps do not end with 1, as the array would be left
+/// unsorted.
+// ANCHOR: shellsort
+pub fn shellsort_with_by<T, G, F>(arr: &mut [T], gaps: &G, compare: F)
+where
+    G: GapSequence + ?Sized,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
     let len = arr.len();
-    for gap in MARCIN_GAPS.iter() {
-        let mut i = *gap; // Type of gap is `&usize`. Deference it!
+    let gaps = gaps.gaps(len);
+    assert!(
+        len < 2 || gaps.last() == Some(&1),
+        "gap sequence must end with 1"
+    );
+    for gap in gaps {
+        // 1
+        let mut i = gap;
         while i < len {
+            // 2
             let mut j = i;
-            while j >= *gap && arr[j - gap] > arr[j] {
-                arr.swap(j - *gap, j);
-                j -= *gap;
+            while j >= gap && compare(&arr[j - gap], &arr[j]) == Ordering::Greater {
+                // 3
+                swap(arr, j - gap, j);
+                j -= gap;
             }
             i += 1;
         }
     }
 }
+// ANCHOR_END: shellsort
 
 #[cfg(test)]
 mod base {
     use super::*;
     base_cases!(shellsort);
+    by_cases!(shellsort_by, shellsort_by_key);
+}
+
+#[cfg(test)]
+mod gap_sequences {
+    use super::*;
+    use crate::sorting::{Pattern, SortStats};
+
+    #[test]
+    fn sequences() {
+        assert_eq!(Shell.gaps(100), [50, 25, 12, 6, 3, 1]);
+        assert_eq!(Knuth.gaps(100), [13, 4, 1]);
+        assert_eq!(Sedgewick.gaps(300), [281, 77, 23, 8, 1]);
+        assert_eq!(Tokuda.gaps(300), [233, 103, 46, 20, 9, 4, 1]);
+        assert_eq!(
+            Ciura.gaps(5000),
+            [3548, 1577, 701, 301, 132, 57, 23, 10, 4, 1]
+        );
+        assert_eq!(MARCIN_GAPS.gaps(30), [23, 10, 4, 1]);
+        for len in 0..2 {
+            assert!(Shell.gaps(len).is_empty());
+            assert!(Ciura.gaps(len).is_empty());
+        }
+    }
+
+    #[test]
+    fn missing_last_gap() {
+        assert_eq!([4, 2].gaps(10), [4, 2, 1]);
+        assert_eq!([4, 2, 0].gaps(10), [4, 2, 1]);
+        assert_eq!([701, 4].gaps(3), [1]);
+        assert!([4, 2].gaps(1).is_empty());
+        let mut arr = [9, 2, 7, 4, 5, 6, 3, 8, 1, 0];
+        shellsort_with(&mut arr, &[4, 2]);
+        assert_eq!(arr, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    #[should_panic(expected = "gap sequence must end with 1")]
+    fn sequence_without_one() {
+        struct Halves;
+        impl GapSequence for Halves {
+            fn gaps(&self, len: usize) -> Vec<usize> {
+                vec![len / 2]
+            }
+        }
+        shellsort_with(&mut [3, 2, 1, 0], &Halves);
+    }
+
+    #[test]
+    fn huge_lengths() {
+        // No overflow when generating gaps for any length.
+        let sequences: [&dyn GapSequence; 5] = [&Shell, &Knuth, &Sedgewick, &Tokuda, &Ciura];
+        for seq in sequences.iter() {
+            let gaps = seq.gaps(usize::MAX);
+            assert_eq!(gaps.last(), Some(&1));
+            assert!(gaps.windows(2).all(|w| w[0] > w[1]));
+        }
+    }
+
+    #[test]
+    fn comparison_counts() {
+        // Fixed seeds, so the totals are the same on every run.
+        let inputs: Vec<_> = (0..4)
+            .map(|seed| Pattern::Random.generate(20_000, seed))
+            .collect();
+        let count = |gaps: &dyn GapSequence| {
+            let mut total = 0;
+            for input in &inputs {
+                let mut arr = input.clone();
+                let stats = SortStats::collect(|| shellsort_with(&mut arr, gaps));
+                assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+                total += stats.comparisons;
+            }
+            total
+        };
+        let shell = count(&Shell);
+        let knuth = count(&Knuth);
+        let sedgewick = count(&Sedgewick);
+        let tokuda = count(&Tokuda);
+        let ciura = count(&Ciura);
+        let marcin = count(&MARCIN_GAPS);
+        // Tokuda's and Ciura's sequences beat the others on random inputs.
+        for &other in &[shell, knuth, sedgewick, marcin] {
+            assert!(tokuda < other && ciura < other);
+        }
+    }
 }
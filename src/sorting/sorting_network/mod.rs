@@ -0,0 +1,546 @@
+use std::cmp::Ordering;
+use std::ptr;
+
+use crate::sorting::{stats, trace};
+
+/// Sorting network for `N` elements, up to 16.
+///
+/// A sorting network is a fixed sequence of compare-exchange operations,
+/// which does not depend on the input, so the same comparisons are made
+/// every time. Comparators in the same layer touch disjoint positions and
+/// could run in parallel.
+///
+/// Networks here have the fewest comparators known: proven optimal for
+/// `N` up to 12, and the best known for 13 to 16.
+///
+/// Not stable.
+///
+/// ```
+/// use rust_algorithm_club::sorting::sort_network;
+///
+/// let mut arr = [4, 1, 3, 5, 2];
+/// sort_network(&mut arr);
+/// assert_eq!(arr, [1, 2, 3, 4, 5]);
+/// ```
+///
+/// There is no network for more than 16 elements, which fails to compile:
+///
+/// ```compile_fail
+/// use rust_algorithm_club::sorting::sort_network;
+///
+/// sort_network(&mut [0; 17]);
+/// ```
+///
+/// Reference: Bert Dobbelaere, "Smallest and fastest sorting networks for a
+/// given number of inputs".
+pub fn sort_network<T: Ord, const N: usize>(arr: &mut [T; N]) {
+    sort_network_by(arr, T::cmp);
+}
+
+/// Sorting network for `N` elements with a comparator function.
+// ANCHOR: sort_network
+pub fn sort_network_by<T, F, const N: usize>(arr: &mut [T; N], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    for &(i, j) in Network::<N>::COMPARATORS {
+        compare_exchange(arr, i, j, &mut compare);
+    }
+}
+// ANCHOR_END: sort_network
+
+/// Sorting network for `N` elements with a key extraction function.
+pub fn sort_network_by_key<T, K, F, const N: usize>(arr: &mut [T; N], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_network_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Sorting network for `N` elements.
+// ANCHOR: network
+struct Network<const N: usize>;
+
+impl<const N: usize> Network<N> {
+    /// Comparators of the network, checked at compile time.
+    const COMPARATORS: &'static [(usize, usize)] = {
+        assert!(
+            N < NETWORKS.len(),
+            "no sorting network for more than 16 elements"
+        );
+        NETWORKS[N]
+    };
+}
+// ANCHOR_END: network
+
+/// Comparators `(i, j)` of sorting networks, indexed by the number of
+/// elements. Each line is a layer.
+#[rustfmt::skip]
+const NETWORKS: [&[(usize, usize)]; 17] = [
+    // 0
+    &[],
+    // 1
+    &[],
+    // 2: 1 comparator, 1 layer
+    &[
+        (0, 1),
+    ],
+    // 3: 3 comparators, 3 layers
+    &[
+        (0, 2),
+        (0, 1),
+        (1, 2),
+    ],
+    // 4: 5 comparators, 3 layers
+    &[
+        (0, 2), (1, 3),
+        (0, 1), (2, 3),
+        (1, 2),
+    ],
+    // 5: 9 comparators, 5 layers
+    &[
+        (0, 3), (1, 4),
+        (0, 2), (1, 3),
+        (0, 1), (2, 4),
+        (1, 2), (3, 4),
+        (2, 3),
+    ],
+    // 6: 12 comparators, 5 layers
+    &[
+        (0, 5), (1, 3), (2, 4),
+        (1, 2), (3, 4),
+        (0, 3), (2, 5),
+        (0, 1), (2, 3), (4, 5),
+        (1, 2), (3, 4),
+    ],
+    // 7: 16 comparators, 6 layers
+    &[
+        (0, 6), (2, 3), (4, 5),
+        (0, 2), (1, 4), (3, 6),
+        (0, 1), (2, 5), (3, 4),
+        (1, 2), (4, 6),
+        (2, 3), (4, 5),
+        (1, 2), (3, 4), (5, 6),
+    ],
+    // 8: 19 comparators, 6 layers
+    &[
+        (0, 2), (1, 3), (4, 6), (5, 7),
+        (0, 4), (1, 5), (2, 6), (3, 7),
+        (0, 1), (2, 3), (4, 5), (6, 7),
+        (2, 4), (3, 5),
+        (1, 4), (3, 6),
+        (1, 2), (3, 4), (5, 6),
+    ],
+    // 9: 25 comparators, 7 layers
+    &[
+        (0, 3), (1, 7), (2, 5), (4, 8),
+        (0, 7), (2, 4), (3, 8), (5, 6),
+        (0, 2), (1, 3), (4, 5), (7, 8),
+        (1, 4), (3, 6), (5, 7),
+        (0, 1), (2, 4), (3, 5), (6, 8),
+        (2, 3), (4, 5), (6, 7),
+        (1, 2), (3, 4), (5, 6),
+    ],
+    // 10: 29 comparators, 8 layers
+    &[
+        (0, 8), (1, 9), (2, 7), (3, 5), (4, 6),
+        (0, 2), (1, 4), (5, 8), (7, 9),
+        (0, 3), (2, 4), (5, 7), (6, 9),
+        (0, 1), (3, 6), (8, 9),
+        (1, 5), (2, 3), (4, 8), (6, 7),
+        (1, 2), (3, 5), (4, 6), (7, 8),
+        (2, 3), (4, 5), (6, 7),
+        (3, 4), (5, 6),
+    ],
+    // 11: 35 comparators, 8 layers
+    &[
+        (0, 9), (1, 6), (2, 4), (3, 7), (5, 8),
+        (0, 1), (3, 5), (4, 10), (6, 9), (7, 8),
+        (1, 3), (2, 5), (4, 7), (8, 10),
+        (0, 4), (1, 2), (3, 7), (5, 9), (6, 8),
+        (0, 1), (2, 6), (4, 5), (7, 8), (9, 10),
+        (2, 4), (3, 6), (5, 7), (8, 9),
+        (1, 2), (3, 4), (5, 6), (7, 8),
+        (2, 3), (4, 5), (6, 7),
+    ],
+    // 12: 39 comparators, 9 layers
+    &[
+        (0, 8), (1, 7), (2, 6), (3, 11), (4, 10), (5, 9),
+        (0, 1), (2, 5), (3, 4), (6, 9), (7, 8), (10, 11),
+        (0, 2), (1, 6), (5, 10), (9, 11),
+        (0, 3), (1, 2), (4, 6), (5, 7), (8, 11), (9, 10),
+        (1, 4), (3, 5), (6, 8), (7, 10),
+        (1, 3), (2, 5), (6, 9), (8, 10),
+        (2, 3), (4, 5), (6, 7), (8, 9),
+        (4, 6), (5, 7),
+        (3, 4), (5, 6), (7, 8),
+    ],
+    // 13: 45 comparators, 10 layers
+    &[
+        (0, 12), (1, 10), (2, 9), (3, 7), (5, 11), (6, 8),
+        (1, 6), (2, 3), (4, 11), (7, 9), (8, 10),
+        (0, 4), (1, 2), (3, 6), (7, 8), (9, 10), (11, 12),
+        (4, 6), (5, 9), (8, 11), (10, 12),
+        (0, 5), (3, 8), (4, 7), (6, 11), (9, 10),
+        (0, 1), (2, 5), (6, 9), (7, 8), (10, 11),
+        (1, 3), (2, 4), (5, 6), (9, 10),
+        (1, 2), (3, 4), (5, 7), (6, 8),
+        (2, 3), (4, 5), (6, 7), (8, 9),
+        (3, 4), (5, 6),
+    ],
+    // 14: 51 comparators, 10 layers
+    &[
+        (0, 1), (2, 3), (4, 5), (6, 7), (8, 9), (10, 11), (12, 13),
+        (0, 2), (1, 3), (4, 8), (5, 9), (10, 12), (11, 13),
+        (0, 4), (1, 2), (3, 7), (5, 8), (6, 10), (9, 13), (11, 12),
+        (0, 6), (1, 5), (3, 9), (4, 10), (7, 13), (8, 12),
+        (2, 10), (3, 11), (4, 6), (7, 9),
+        (1, 3), (2, 8), (5, 11), (6, 7), (10, 12),
+        (1, 4), (2, 6), (3, 5), (7, 11), (8, 10), (9, 12),
+        (2, 4), (3, 6), (5, 8), (7, 10), (9, 11),
+        (3, 4), (5, 6), (7, 8), (9, 10),
+        (6, 7),
+    ],
+    // 15: 56 comparators, 10 layers
+    &[
+        (0, 13), (1, 12), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10),
+        (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (11, 12),
+        (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13),
+        (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14),
+        (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14),
+        (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14),
+        (2, 4), (3, 6), (9, 12), (11, 13),
+        (3, 5), (6, 8), (7, 9), (10, 12),
+        (3, 4), (5, 6), (7, 8), (9, 10), (11, 12),
+        (6, 7), (8, 9),
+    ],
+    // 16: 60 comparators, 10 layers
+    &[
+        (0, 13), (1, 12), (2, 15), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10),
+        (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (10, 15), (11, 12),
+        (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (14, 15),
+        (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14), (13, 15),
+        (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14),
+        (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14),
+        (2, 4), (3, 6), (9, 12), (11, 13),
+        (3, 5), (6, 8), (7, 9), (10, 12),
+        (3, 4), (5, 6), (7, 8), (9, 10), (11, 12),
+        (6, 7), (8, 9),
+    ],
+];
+
+/// Bitonic sort.
+///
+/// A bitonic sequence rises then falls. Two sorted halves in opposite
+/// directions form one, and a bitonic merge sorts it: compare-exchange each
+/// element with the one half the length away, then merge both halves
+/// recursively. Sorting halves in opposite directions recursively sorts the
+/// whole slice.
+///
+/// This is a sorting network of O(n log² n) comparators in O(log² n)
+/// layers, popular on GPUs for its regular structure.
+///
+/// Not stable.
+///
+/// # Panics
+///
+/// Panics if the length is not a power of two.
+pub fn bitonic_sort<T: Ord>(arr: &mut [T]) {
+    bitonic_sort_by(arr, T::cmp);
+}
+
+/// Bitonic sort with a comparator function.
+// ANCHOR: bitonic_sort
+pub fn bitonic_sort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = arr.len();
+    assert!(
+        len == 0 || len.is_power_of_two(),
+        "length must be a power of two"
+    );
+    let mut compare = stats::counting(compare);
+    // Blocks of `size` are sorted alternately ascending and descending, then
+    // merged by comparing elements `dist` apart.
+    let mut size = 2;
+    while size <= len {
+        let mut dist = size / 2;
+        while dist > 0 {
+            for i in 0..len {
+                let j = i ^ dist;
+                if j <= i {
+                    continue;
+                }
+                if i & size == 0 {
+                    compare_exchange(arr, i, j, &mut compare);
+                } else {
+                    compare_exchange(arr, j, i, &mut compare);
+                }
+            }
+            dist /= 2;
+        }
+        size *= 2;
+    }
+}
+// ANCHOR_END: bitonic_sort
+
+/// Bitonic sort with a key extraction function.
+pub fn bitonic_sort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    bitonic_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Batcher's odd-even merge sort.
+///
+/// Sort both halves recursively, then merge them by merging elements at
+/// even and odd positions separately and fixing up neighbors. Like bitonic
+/// sort it is a sorting network of O(n log² n) comparators, but with fewer
+/// of them.
+///
+/// Every comparator puts the smaller element at the lower position, so the
+/// network of the next power of two also sorts any shorter length, by
+/// dropping comparators out of bounds as if those positions held the
+/// greatest elements.
+///
+/// Not stable.
+pub fn odd_even_mergesort<T: Ord>(arr: &mut [T]) {
+    odd_even_mergesort_by(arr, T::cmp);
+}
+
+/// Batcher's odd-even merge sort with a comparator function.
+// ANCHOR: odd_even_mergesort
+pub fn odd_even_mergesort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = arr.len();
+    let mut compare = stats::counting(compare);
+    // Merge sorted blocks of `size`, comparing elements `dist` apart which
+    // fall in the same merged block of `2 * size`.
+    let mut size = 1;
+    while size < len {
+        let mut dist = size;
+        while dist > 0 {
+            let mut j = dist % size;
+            while j + dist < len {
+                for i in j..(j + dist).min(len - dist) {
+                    if i / (2 * size) == (i + dist) / (2 * size) {
+                        compare_exchange(arr, i, i + dist, &mut compare);
+                    }
+                }
+                j += 2 * dist;
+            }
+            dist /= 2;
+        }
+        size *= 2;
+    }
+}
+// ANCHOR_END: odd_even_mergesort
+
+/// Batcher's odd-even merge sort with a key extraction function.
+pub fn odd_even_mergesort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    odd_even_mergesort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Put the smaller of `arr[i]` and `arr[j]` at `i`.
+///
+/// Without a branch on the result of the comparison: the sources of both
+/// positions are selected, like `min` and `max`, so the same instructions
+/// run whatever the input is.
+// ANCHOR: compare_exchange
+fn compare_exchange<T, F>(arr: &mut [T], i: usize, j: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let less = compare(&arr[j], &arr[i]) == Ordering::Less;
+    if stats::active() && less {
+        stats::record_swaps(1);
+        trace::swap(arr, i, j);
+    }
+    // Safety: `i` and `j` are in bounds, as checked by the comparison. Both
+    // elements are read out and written back once, and nothing in between
+    // panics.
+    unsafe {
+        let v = arr.as_mut_ptr();
+        let (a, b) = (v.add(i), v.add(j));
+        let (min, max) = if less { (b, a) } else { (a, b) };
+        let (min, max) = (ptr::read(min), ptr::read(max));
+        ptr::write(a, min);
+        ptr::write(b, max);
+    }
+}
+// ANCHOR_END: compare_exchange
+
+#[cfg(test)]
+mod base {
+    use super::*;
+    base_cases!(odd_even_mergesort);
+    by_cases!(odd_even_mergesort_by, odd_even_mergesort_by_key);
+}
+
+#[cfg(test)]
+mod zero_one {
+    //! By the 0-1 principle, a network sorts all inputs if it sorts all
+    //! `2^n` sequences of zeros and ones.
+
+    use super::*;
+
+    fn bits(mask: u32, len: usize) -> Vec<u8> {
+        (0..len).map(|i| (mask >> i & 1) as u8).collect()
+    }
+
+    fn is_sorted(arr: &[u8]) -> bool {
+        arr.windows(2).all(|w| w[0] <= w[1])
+    }
+
+    fn check_network<const N: usize>() {
+        for mask in 0..1 << N {
+            let mut arr = [0; N];
+            arr.copy_from_slice(&bits(mask, N));
+            sort_network(&mut arr);
+            assert!(is_sorted(&arr), "N = {}, input {:0b}", N, mask);
+        }
+    }
+
+    #[test]
+    fn networks() {
+        check_network::<0>();
+        check_network::<1>();
+        check_network::<2>();
+        check_network::<3>();
+        check_network::<4>();
+        check_network::<5>();
+        check_network::<6>();
+        check_network::<7>();
+        check_network::<8>();
+        check_network::<9>();
+        check_network::<10>();
+        check_network::<11>();
+        check_network::<12>();
+        check_network::<13>();
+        check_network::<14>();
+        check_network::<15>();
+        check_network::<16>();
+    }
+
+    #[test]
+    fn network_sizes() {
+        let sizes: Vec<_> = NETWORKS.iter().map(|n| n.len()).collect();
+        assert_eq!(
+            sizes,
+            [0, 0, 1, 3, 5, 9, 12, 16, 19, 25, 29, 35, 39, 45, 51, 56, 60]
+        );
+        for (len, network) in NETWORKS.iter().enumerate() {
+            assert!(network.iter().all(|&(i, j)| i < j && j < len));
+        }
+    }
+
+    #[test]
+    fn bitonic() {
+        for len in [0, 1, 2, 4, 8, 16].iter().copied() {
+            for mask in 0..1 << len {
+                let mut arr = bits(mask, len);
+                bitonic_sort(&mut arr);
+                assert!(is_sorted(&arr), "len = {}, input {:0b}", len, mask);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_even() {
+        for len in 0..=16 {
+            for mask in 0..1 << len {
+                let mut arr = bits(mask, len);
+                odd_even_mergesort(&mut arr);
+                assert!(is_sorted(&arr), "len = {}, input {:0b}", len, mask);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::{Pattern, SortStats};
+
+    #[test]
+    fn network_values() {
+        let mut arr = [5, -3, 9, 0, 9, 2, -8, 7, 1, 1, 4, 6, -2, 3, 8, -5];
+        let mut res = arr;
+        res.sort();
+        sort_network(&mut arr);
+        assert_eq!(arr, res);
+
+        let mut arr = ["pear", "fig", "apple"];
+        sort_network_by(&mut arr, |a, b| b.cmp(a));
+        assert_eq!(arr, ["pear", "fig", "apple"]);
+        sort_network_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["fig", "pear", "apple"]);
+    }
+
+    #[test]
+    fn owned_values() {
+        let mut arr = ["d", "b", "e", "a", "c"].map(String::from);
+        let stats = SortStats::collect(|| sort_network(&mut arr));
+        assert_eq!(arr, ["a", "b", "c", "d", "e"]);
+        assert!(stats.swaps > 0 && stats.swaps <= NETWORKS[5].len());
+    }
+
+    #[test]
+    fn oblivious() {
+        // The same comparisons whatever the input.
+        for pattern in Pattern::ALL.iter() {
+            let arr = pattern.generate(12, 0);
+            let mut arr: [i32; 12] = std::convert::TryInto::try_into(arr).unwrap();
+            let stats = SortStats::collect(|| sort_network(&mut arr));
+            assert_eq!(stats.comparisons, 39);
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        for pattern in Pattern::ALL.iter() {
+            for &len in &[64, 1024] {
+                let arr = pattern.generate(len, 1);
+                let mut res = arr.clone();
+                res.sort();
+                let mut bitonic = arr.clone();
+                bitonic_sort(&mut bitonic);
+                assert_eq!(bitonic, res);
+                let mut odd_even = arr.clone();
+                odd_even_mergesort(&mut odd_even);
+                assert_eq!(odd_even, res);
+            }
+            let arr = pattern.generate(1000, 1);
+            let mut res = arr.clone();
+            res.sort();
+            let mut odd_even = arr.clone();
+            odd_even_mergesort(&mut odd_even);
+            assert_eq!(odd_even, res);
+        }
+    }
+
+    #[test]
+    fn fewer_comparators_than_bitonic() {
+        let mut arr = Pattern::Random.generate(1024, 2);
+        let bitonic = SortStats::collect(|| bitonic_sort(&mut arr.clone()));
+        let odd_even = SortStats::collect(|| odd_even_mergesort(&mut arr));
+        assert!(odd_even.comparisons < bitonic.comparisons);
+    }
+
+    #[test]
+    #[should_panic(expected = "length must be a power of two")]
+    fn bitonic_not_power_of_two() {
+        bitonic_sort(&mut [3, 2, 1]);
+    }
+}
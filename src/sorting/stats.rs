@@ -0,0 +1,334 @@
+//! Instrumentation of sorting algorithms.
+//!
+//! Sorting algorithms in this crate report their work to a thread-local
+//! collector, which is activated by `SortStats::collect`. When nothing is
+//! collected or traced, every hook returns after checking a single
+//! thread-local flag.
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::ops::Add;
+
+use crate::sorting::trace;
+
+/// Statistics of the work done by sorting algorithms.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{bubble_sort, SortStats};
+///
+/// let mut arr = [3, 2, 1];
+/// let stats = SortStats::collect(|| bubble_sort(&mut arr));
+/// assert_eq!(stats.comparisons, 6);
+/// assert_eq!(stats.swaps, 3);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortStats {
+    /// Number of element comparisons.
+    pub comparisons: usize,
+    /// Number of element swaps.
+    pub swaps: usize,
+    /// Number of element moves other than swaps, such as copying elements
+    /// into or out of an auxiliary buffer.
+    pub moves: usize,
+    /// Number of auxiliary heap allocations.
+    pub allocations: usize,
+}
+
+impl SortStats {
+    /// Run `f` and collect statistics of all sorts it runs on current thread.
+    ///
+    /// Collections can be nested. Statistics of an inner collection are also
+    /// added to the outer one.
+    pub fn collect<F: FnOnce()>(f: F) -> SortStats {
+        let outer = STATS.with(|s| s.replace(Some(SortStats::default())));
+        // Restore the outer collection even if `f` panics.
+        let guard = Restore(outer);
+        let active = Active::new();
+        f();
+        drop(active);
+        let stats = STATS.with(|s| s.get()).unwrap_or_default();
+        drop(guard);
+        record_stats(stats);
+        stats
+    }
+}
+
+impl Add for SortStats {
+    type Output = SortStats;
+
+    fn add(self, other: SortStats) -> SortStats {
+        SortStats {
+            comparisons: self.comparisons + other.comparisons,
+            swaps: self.swaps + other.swaps,
+            moves: self.moves + other.moves,
+            allocations: self.allocations + other.allocations,
+        }
+    }
+}
+
+thread_local! {
+    /// Active collection. `None` if no one is collecting.
+    static STATS: Cell<Option<SortStats>> = const { Cell::new(None) };
+    /// Whether a counting comparator is running.
+    static COMPARING: Cell<bool> = const { Cell::new(false) };
+    /// Number of active collections and traces.
+    static ACTIVE: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether statistics are collected or steps are traced on current thread.
+#[inline]
+pub(crate) fn active() -> bool {
+    ACTIVE.with(|a| a.get() > 0)
+}
+
+/// Guard keeping hooks active while alive.
+pub(crate) struct Active;
+
+impl Active {
+    pub(crate) fn new() -> Self {
+        ACTIVE.with(|a| a.set(a.get() + 1));
+        Active
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        ACTIVE.with(|a| a.set(a.get() - 1));
+    }
+}
+
+/// Guard restoring the outer collection.
+struct Restore(Option<SortStats>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        STATS.with(|s| s.set(self.0));
+    }
+}
+
+/// Guard resetting the comparing flag.
+struct Comparing;
+
+impl Drop for Comparing {
+    fn drop(&mut self) {
+        COMPARING.with(|c| c.set(false));
+    }
+}
+
+/// Update the active collection, if any.
+#[inline]
+fn record<F: FnOnce(&mut SortStats)>(f: F) {
+    if !active() {
+        return;
+    }
+    STATS.with(|s| {
+        if let Some(mut stats) = s.get() {
+            f(&mut stats);
+            s.set(Some(stats));
+        }
+    })
+}
+
+/// Wrap a comparator to count comparisons.
+///
+/// Sorts delegating to other sorts wrap the same comparator more than once.
+/// Only the outermost wrapper counts, so a comparison is counted once.
+pub(crate) fn counting<T, F>(mut compare: F) -> impl FnMut(&T, &T) -> Ordering
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    move |a, b| {
+        if !active() || COMPARING.with(|c| c.replace(true)) {
+            return compare(a, b);
+        }
+        let _guard = Comparing;
+        record(|s| s.comparisons += 1);
+        trace::compare(a, b);
+        compare(a, b)
+    }
+}
+
+/// Swap two elements of a slice, and record it.
+#[inline]
+pub(crate) fn swap<T>(arr: &mut [T], a: usize, b: usize) {
+    if active() {
+        record(|s| s.swaps += 1);
+        trace::swap(arr, a, b);
+    }
+    arr.swap(a, b);
+}
+
+/// Record `n` swaps done in bulk, such as reversing a slice.
+pub(crate) fn record_swaps(n: usize) {
+    record(|s| s.swaps += n);
+}
+
+/// Record `n` element moves.
+pub(crate) fn record_moves(n: usize) {
+    record(|s| s.moves += n);
+}
+
+/// Record an auxiliary allocation.
+pub(crate) fn record_allocation() {
+    record(|s| s.allocations += 1);
+}
+
+/// Record statistics collected elsewhere, such as on another thread.
+pub(crate) fn record_stats(stats: SortStats) {
+    record(|s| *s = *s + stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::*;
+
+    fn random(len: usize) -> Vec<i32> {
+        (0..len).map(|_| rand::random::<i32>() % 1000).collect()
+    }
+
+    type Sort = fn(&mut [i32]);
+
+    fn stats_of(algo: Sort, arr: &[i32]) -> SortStats {
+        let mut arr = arr.to_vec();
+        SortStats::collect(|| algo(&mut arr))
+    }
+
+    #[test]
+    fn no_collection() {
+        let mut arr = [3, 2, 1];
+        bubble_sort(&mut arr);
+        assert_eq!(STATS.with(|s| s.get()), None);
+    }
+
+    #[test]
+    fn hooks_inactive_outside_collection() {
+        assert!(!active());
+        SortStats::collect(|| {
+            assert!(active());
+            SortStats::collect(|| assert!(active()));
+            assert!(active());
+        });
+        assert!(!active());
+        let _ = std::panic::catch_unwind(|| SortStats::collect(|| panic!()));
+        assert!(!active());
+    }
+
+    #[test]
+    fn bubble_sort_exact() {
+        let stats = stats_of(bubble_sort, &[3, 2, 1]);
+        assert_eq!(
+            stats,
+            SortStats {
+                comparisons: 6,
+                swaps: 3,
+                moves: 0,
+                allocations: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let mut a = [2, 1];
+        let mut b = [2, 1];
+        let mut inner = SortStats::default();
+        let outer = SortStats::collect(|| {
+            insertion_sort(&mut a);
+            inner = SortStats::collect(|| insertion_sort(&mut b));
+        });
+        assert_eq!(inner.comparisons, 1);
+        assert_eq!(outer.comparisons, 2);
+        assert_eq!(outer.swaps, 2);
+    }
+
+    #[test]
+    fn delegated_comparisons_counted_once() {
+        // Introsort runs insertion sort on short slices.
+        let mut arr = [1, 2, 3];
+        let stats = SortStats::collect(|| introsort(&mut arr));
+        assert_eq!(stats.comparisons, 2);
+    }
+
+    #[test]
+    fn bubble_sort_optimized_fewer_comparisons() {
+        let sorted: Vec<_> = (0..100).collect();
+        let reversed: Vec<_> = (0..100).rev().collect();
+
+        // Both stop after the first pass on sorted input.
+        let naive = stats_of(bubble_sort, &sorted);
+        let optimized = stats_of(bubble_sort_optimized, &sorted);
+        assert_eq!(naive.comparisons, 99);
+        assert_eq!(optimized.comparisons, 99);
+
+        // Remembering the last swap skips the sorted tail.
+        let naive = stats_of(bubble_sort, &reversed);
+        let optimized = stats_of(bubble_sort_optimized, &reversed);
+        assert!(optimized.comparisons < naive.comparisons);
+        assert_eq!(optimized.swaps, naive.swaps);
+
+        let arr = random(100);
+        let naive = stats_of(bubble_sort, &arr);
+        let optimized = stats_of(bubble_sort_optimized, &arr);
+        assert!(optimized.comparisons < naive.comparisons);
+    }
+
+    #[test]
+    fn binary_insertion_sort_fewer_comparisons() {
+        let arr = random(200);
+        let linear = stats_of(insertion_sort, &arr);
+        let binary = stats_of(binary_insertion_sort, &arr);
+        assert!(binary.comparisons < linear.comparisons);
+        // Both shift elements the same way.
+        assert_eq!(binary.swaps, linear.swaps);
+    }
+
+    #[test]
+    fn auxiliary_allocations() {
+        let arr = random(1000);
+        assert_eq!(stats_of(heapsort, &arr).allocations, 0);
+        assert_eq!(stats_of(quicksort, &arr).allocations, 0);
+
+        let stats = stats_of(mergesort, &arr);
+        assert_eq!(stats.allocations, 1);
+        assert!(stats.moves > 0);
+        assert_eq!(stats.swaps, 0);
+
+        let stats = stats_of(timsort, &arr);
+        assert_eq!(stats.allocations, 2);
+        assert!(stats.moves > 0);
+    }
+
+    #[test]
+    fn every_sort() {
+        let algos: &[(&str, Sort)] = &[
+            ("bubble_sort", bubble_sort),
+            ("bubble_sort_optimized", bubble_sort_optimized),
+            ("insertion_sort", insertion_sort),
+            ("binary_insertion_sort", binary_insertion_sort),
+            ("selection_sort", selection_sort),
+            ("shellsort", shellsort),
+            ("heapsort", heapsort),
+            ("mergesort", mergesort),
+            ("mergesort_bottom_up", mergesort_bottom_up),
+            ("quicksort", quicksort),
+            ("quicksort_optimized", quicksort_optimized),
+            ("quicksort_manual_tco", quicksort_manual_tco),
+            ("quicksort_3way", quicksort_3way),
+            ("quicksort_hoare", quicksort_hoare),
+            ("timsort", timsort),
+            ("introsort", introsort),
+            ("pdqsort", pdqsort),
+        ];
+        let arr = random(500);
+        for (name, algo) in algos {
+            let stats = stats_of(*algo, &arr);
+            assert!(stats.comparisons > 0, "{} comparisons", name);
+            assert!(stats.swaps + stats.moves > 0, "{} swaps and moves", name);
+        }
+
+        let stats = stats_of(radix_sort, &[38, 5, 21, 7, 13]);
+        assert_eq!(stats.comparisons, 0);
+        assert!(stats.moves > 0);
+    }
+}
@@ -1,9 +1,157 @@
-/// Common test cases for any sorting algorithms accepting i32 array as input.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+/// Element types which can be built from plain test case values.
+///
+/// Test cases are written in `i32` or `(i32, i32)`, and converted into
+/// every element type under test.
+pub(crate) trait Case<C>: Debug {
+    /// Build an element from a test case value.
+    fn from_case(case: C) -> Self;
+    /// Convert an element back for assertion.
+    fn to_case(&self) -> C;
+}
+
+impl Case<i32> for i32 {
+    fn from_case(case: i32) -> Self {
+        case
+    }
+    fn to_case(&self) -> i32 {
+        *self
+    }
+}
+
+/// Zero-padded to keep lexicographic order same as numeric order.
+impl Case<i32> for String {
+    fn from_case(case: i32) -> Self {
+        format!("{:04}", case)
+    }
+    fn to_case(&self) -> i32 {
+        self.parse().unwrap()
+    }
+}
+
+/// A value neither `Copy` nor `Clone`, to ensure sorts only require `Ord`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Item(Box<i32>);
+
+impl Case<i32> for Item {
+    fn from_case(case: i32) -> Self {
+        Item(Box::new(case))
+    }
+    fn to_case(&self) -> i32 {
+        *self.0
+    }
+}
+
+impl Case<(i32, i32)> for (i32, i32) {
+    fn from_case(case: (i32, i32)) -> Self {
+        case
+    }
+    fn to_case(&self) -> (i32, i32) {
+        *self
+    }
+}
+
+/// A non-`Clone` record ordered by its key only. Records with equal keys
+/// compare equal, so only a stable sort keeps their original order.
+#[derive(Debug)]
+pub(crate) struct Keyed {
+    key: i32,
+    value: String,
+}
+
+impl PartialEq for Keyed {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Keyed {}
+
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Keyed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl Case<(i32, i32)> for Keyed {
+    fn from_case((key, value): (i32, i32)) -> Self {
+        Keyed {
+            key,
+            value: value.to_string(),
+        }
+    }
+    fn to_case(&self) -> (i32, i32) {
+        (self.key, self.value.parse().unwrap())
+    }
+}
+
+/// System allocator counting allocations made by each thread.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Threads being torn down have no counter. Ignore them.
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Count heap allocations made by `f` on current thread.
+pub(crate) fn allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Common test cases for any sorting algorithms.
+///
+/// Cases are run against `i32`, `String` and a non-`Clone` type by default.
+/// Algorithms not generic over `Ord` can specify element types explicitly,
+/// such as `base_cases!(radix_sort, i32)`.
 macro_rules! base_cases {
     ($algo:ident) => {
+        base_cases!(
+            $algo,
+            i32,
+            String,
+            $crate::sorting::test_cases::Item
+        );
+    };
+    ($algo:ident, $($ty:ty),+) => {
         fn assert(arr: &mut [i32], res: &[i32]) {
-            $algo(arr);
-            assert_eq!(arr, res);
+            use $crate::sorting::test_cases::Case;
+            $(
+                let mut values: Vec<$ty> = arr.iter().map(|v| Case::from_case(*v)).collect();
+                $algo(&mut values[..]);
+                let values: Vec<i32> = values.iter().map(Case::to_case).collect();
+                assert_eq!(values, res, "sorting {}", stringify!($ty));
+            )+
         }
 
         #[test]
@@ -125,13 +273,23 @@ macro_rules! base_cases {
 /// Test cases for validate stability of sorting algorithm.
 /// The input value is a tuple type of (i32, i32).
 ///
-/// To test with these cases, an algorithm must accept generic input values.
+/// Cases are run against `(i32, i32)` and a non-`Clone` record compared by
+/// key only by default. Element types can be specified explicitly, such as
+/// `stability_cases!(counting_sort_, (i32, i32))`.
 macro_rules! stability_cases {
     ($algo:ident) => {
+        stability_cases!($algo, (i32, i32), $crate::sorting::test_cases::Keyed);
+    };
+    ($algo:ident, $($ty:ty),+) => {
         /// (key, value)
         fn assert_stability(arr: &mut [(i32, i32)], res: &[(i32, i32)]) {
-            $algo(arr);
-            assert_eq!(arr, res);
+            use $crate::sorting::test_cases::Case;
+            $(
+                let mut values: Vec<$ty> = arr.iter().map(|v| Case::from_case(*v)).collect();
+                $algo(&mut values[..]);
+                let values: Vec<(i32, i32)> = values.iter().map(Case::to_case).collect();
+                assert_eq!(values, res, "sorting {}", stringify!($ty));
+            )+
         }
 
         #[test]
@@ -213,3 +371,48 @@ macro_rules! stability_cases {
         }
     };
 }
+
+/// Test cases for `_by` and `_by_key` variants of sorting algorithms.
+///
+/// * `$algo_by` - Sort with a comparator, like `slice::sort_by`.
+/// * `$algo_by_key` - Sort with a key extractor, like `slice::sort_by_key`.
+macro_rules! by_cases {
+    ($algo_by:ident, $algo_by_key:ident) => {
+        #[test]
+        fn by_descending() {
+            let mut arr = [1, 5, 3, 3, 4, 1, 3, 4];
+            $algo_by(&mut arr, |a, b| b.cmp(a));
+            assert_eq!(arr, [5, 4, 4, 3, 3, 3, 1, 1]);
+        }
+
+        #[test]
+        fn by_floats() {
+            let mut arr = [2.5, -0.5, 3.0, f64::INFINITY, 1.0, -7.25, 1.0];
+            $algo_by(&mut arr, |a: &f64, b: &f64| a.total_cmp(b));
+            assert_eq!(arr, [-7.25, -0.5, 1.0, 1.0, 2.5, 3.0, f64::INFINITY]);
+        }
+
+        #[test]
+        fn by_key_string_len() {
+            let mut arr = ["ccc", "a", "dddd", "bb", ""];
+            $algo_by_key(&mut arr, |s| s.len());
+            assert_eq!(arr, ["", "a", "bb", "ccc", "dddd"]);
+        }
+
+        #[test]
+        fn by_key_struct_field() {
+            #[derive(Debug, PartialEq)]
+            struct Person {
+                name: &'static str,
+                age: u32,
+            }
+            let person = |name, age| Person { name, age };
+            let mut arr = [person("Ann", 42), person("Bob", 7), person("Cid", 19)];
+            $algo_by_key(&mut arr, |p| p.age);
+            assert_eq!(
+                arr,
+                [person("Bob", 7), person("Cid", 19), person("Ann", 42)]
+            );
+        }
+    };
+}
@@ -1,4 +1,683 @@
+use std::cmp::{self, Ordering};
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use crate::sorting::insertion_sort::binary_insertion_sort_from;
+use crate::sorting::{stats, trace};
+
+/// Slices shorter than this are sorted by binary insertion sort directly.
+/// This is also the upper bound of minrun.
+const MIN_MERGE: usize = 64;
+
+/// Initial threshold for entering galloping mode during merges.
+const MIN_GALLOP: usize = 7;
+
 /// Timsort.
-pub fn timsort(_arr: &mut [i32]) {
-    unimplemented!()
+///
+/// A stable and adaptive mergesort variant, which
+///
+/// 1. finds natural runs (non-descending or strictly descending) in input,
+/// 2. extends short runs to `minrun` elements with binary insertion sort,
+/// 3. pushes runs onto a stack and merges them while keeping the invariants
+///    `A > B + C` and `B > C` on the top three runs,
+/// 4. switches to galloping mode when one run keeps winning during merges.
+///
+/// Reference: [CPython listsort.txt][1]
+///
+/// [1]: https://github.com/python/cpython/blob/main/Objects/listsort.txt
+pub fn timsort<T: Ord>(arr: &mut [T]) {
+    timsort_by(arr, T::cmp);
+}
+
+/// Timsort with a comparator function.
+pub fn timsort_by<T, F>(arr: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut compare = stats::counting(compare);
+    timsort_impl(arr, &mut |a, b| compare(a, b) == Ordering::Less);
+}
+
+/// Timsort with a key extraction function.
+pub fn timsort_by_key<T, K, F>(arr: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    timsort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Timsort core accepting a strict "less than" predicate.
+fn timsort_impl<T, F>(arr: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    // Zero-sized values are indistinguishable, any order is a stable order.
+    if len < 2 || mem::size_of::<T>() == 0 {
+        return;
+    }
+
+    // 1. Short slices are not worth the run bookkeeping.
+    if len < MIN_MERGE {
+        let run = count_run(arr, is_less);
+        binary_insertion_sort_from(arr, run, is_less);
+        return;
+    }
+
+    let min_run = min_run_length(len);
+    // Every run except the last one has at least `min_run` elements.
+    let mut runs: Vec<Run> = Vec::with_capacity(len / min_run + 1);
+    let mut state = MergeState {
+        buf: Vec::with_capacity(len / 2),
+        min_gallop: MIN_GALLOP,
+    };
+    stats::record_allocation();
+    stats::record_allocation();
+
+    let mut start = 0;
+    while start < len {
+        // 2. Find next natural run, and extend it if it is too short.
+        let mut run_len = count_run(&mut arr[start..], is_less);
+        if run_len < min_run {
+            let end = cmp::min(start + min_run, len);
+            binary_insertion_sort_from(&mut arr[start..end], run_len, is_less);
+            run_len = end - start;
+        }
+
+        // 3. Push the run onto the stack and restore invariants.
+        runs.push(Run {
+            start,
+            len: run_len,
+        });
+        start += run_len;
+        merge_collapse(arr, &mut runs, &mut state, is_less);
+    }
+
+    // 4. Merge all remaining runs.
+    merge_force_collapse(arr, &mut runs, &mut state, is_less);
+    debug_assert!(runs.len() == 1 && runs[0].len == len);
+}
+
+/// A sorted run on the run stack.
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    start: usize,
+    len: usize,
+}
+
+/// States shared across merges.
+struct MergeState<T> {
+    /// Scratch buffer. Its length is always 0, only capacity is used.
+    buf: Vec<T>,
+    /// Adaptive threshold for entering galloping mode.
+    min_gallop: usize,
+}
+
+/// Compute minimum run length.
+///
+/// Take the six most significant bits of `n`, and add one if any of the
+/// remaining bits is set. The result lies in `MIN_MERGE / 2..=MIN_MERGE`, and
+/// `n / minrun` is a power of two or slightly less than it, which keeps
+/// merges balanced.
+fn min_run_length(mut n: usize) -> usize {
+    let mut r = 0;
+    while n >= MIN_MERGE {
+        r |= n & 1;
+        n >>= 1;
+    }
+    n + r
+}
+
+/// Find the length of the run starting at the beginning of the slice.
+///
+/// A strictly descending run is reversed in place. Strictness is required to
+/// keep the sort stable.
+fn count_run<T, F>(arr: &mut [T], is_less: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    if len < 2 {
+        return len;
+    }
+    let mut end = 2;
+    if is_less(&arr[1], &arr[0]) {
+        while end < len && is_less(&arr[end], &arr[end - 1]) {
+            end += 1;
+        }
+        arr[..end].reverse();
+        stats::record_swaps(end / 2);
+        trace::writes(&arr[..end]);
+    } else {
+        while end < len && !is_less(&arr[end], &arr[end - 1]) {
+            end += 1;
+        }
+    }
+    end
+}
+
+/// Merge runs on the top of stack until invariants are satisfied:
+///
+/// 1. `runs[n - 3].len > runs[n - 2].len + runs[n - 1].len`
+/// 2. `runs[n - 2].len > runs[n - 1].len`
+///
+/// The invariant is also checked against `runs[n - 4]`, fixing the bug found
+/// in the original algorithm by de Gouw et al.
+fn merge_collapse<T, F>(
+    arr: &mut [T],
+    runs: &mut Vec<Run>,
+    state: &mut MergeState<T>,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    while runs.len() > 1 {
+        let n = runs.len();
+        let mut i = n - 2;
+        if (n >= 3 && runs[n - 3].len <= runs[n - 2].len + runs[n - 1].len)
+            || (n >= 4 && runs[n - 4].len <= runs[n - 3].len + runs[n - 2].len)
+        {
+            if runs[n - 3].len < runs[n - 1].len {
+                i = n - 3;
+            }
+        } else if runs[n - 2].len > runs[n - 1].len {
+            break;
+        }
+        merge_at(arr, runs, i, state, is_less);
+    }
+}
+
+/// Merge all runs on the stack into one.
+fn merge_force_collapse<T, F>(
+    arr: &mut [T],
+    runs: &mut Vec<Run>,
+    state: &mut MergeState<T>,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    while runs.len() > 1 {
+        let n = runs.len();
+        let mut i = n - 2;
+        if n >= 3 && runs[n - 3].len < runs[n - 1].len {
+            i = n - 3;
+        }
+        merge_at(arr, runs, i, state, is_less);
+    }
+}
+
+/// Merge `runs[i]` and `runs[i + 1]`.
+fn merge_at<T, F>(
+    arr: &mut [T],
+    runs: &mut Vec<Run>,
+    i: usize,
+    state: &mut MergeState<T>,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    let a = runs[i];
+    let b = runs.remove(i + 1);
+    runs[i].len += b.len;
+
+    let run = &mut arr[a.start..b.start + b.len];
+    let mid = a.len;
+
+    // Elements in A not greater than B[0] are already in place.
+    let k = gallop_right(&run[mid], &run[..mid], 0, is_less);
+    let run = &mut run[k..];
+    let mid = mid - k;
+    if mid == 0 {
+        return;
+    }
+
+    // Elements in B not less than the last of A are already in place.
+    let b_len = run.len() - mid;
+    let b_len = gallop_left(&run[mid - 1], &run[mid..], b_len - 1, is_less);
+    if b_len == 0 {
+        return;
+    }
+    let run = &mut run[..mid + b_len];
+
+    // Copy the shorter run into the buffer.
+    trace::merge(run, mid);
+    if mid <= b_len {
+        merge_lo(run, mid, state, is_less);
+    } else {
+        merge_hi(run, mid, state, is_less);
+    }
+    trace::writes(run);
+}
+
+/// Locate the position to insert `key` into sorted `arr` before any equal
+/// elements, i.e. the number of elements less than `key`.
+///
+/// Gallop (exponential search) from `hint` then binary search the last
+/// interval. This takes O(log k) comparisons where k is the distance from
+/// `hint` to the result.
+fn gallop_left<T, F>(key: &T, arr: &[T], hint: usize, is_less: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    let mut last = 0;
+    let mut ofs = 1;
+    let (lo, hi);
+    if is_less(&arr[hint], key) {
+        // Gallop right until arr[hint + last] < key <= arr[hint + ofs].
+        let max = len - hint;
+        while ofs < max && is_less(&arr[hint + ofs], key) {
+            last = ofs;
+            ofs = (ofs << 1) + 1;
+        }
+        ofs = cmp::min(ofs, max);
+        lo = hint + last + 1;
+        hi = hint + ofs;
+    } else {
+        // Gallop left until arr[hint - ofs] < key <= arr[hint - last].
+        let max = hint + 1;
+        while ofs < max && !is_less(&arr[hint - ofs], key) {
+            last = ofs;
+            ofs = (ofs << 1) + 1;
+        }
+        ofs = cmp::min(ofs, max);
+        lo = hint + 1 - ofs;
+        hi = hint - last;
+    }
+    lo + arr[lo..hi].partition_point(|x| is_less(x, key))
+}
+
+/// Locate the position to insert `key` into sorted `arr` after any equal
+/// elements, i.e. the number of elements not greater than `key`.
+///
+/// See `gallop_left` for details.
+fn gallop_right<T, F>(key: &T, arr: &[T], hint: usize, is_less: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    let mut last = 0;
+    let mut ofs = 1;
+    let (lo, hi);
+    if is_less(key, &arr[hint]) {
+        // Gallop left until arr[hint - ofs] <= key < arr[hint - last].
+        let max = hint + 1;
+        while ofs < max && is_less(key, &arr[hint - ofs]) {
+            last = ofs;
+            ofs = (ofs << 1) + 1;
+        }
+        ofs = cmp::min(ofs, max);
+        lo = hint + 1 - ofs;
+        hi = hint - last;
+    } else {
+        // Gallop right until arr[hint + last] <= key < arr[hint + ofs].
+        let max = len - hint;
+        while ofs < max && !is_less(key, &arr[hint + ofs]) {
+            last = ofs;
+            ofs = (ofs << 1) + 1;
+        }
+        ofs = cmp::min(ofs, max);
+        lo = hint + last + 1;
+        hi = hint + ofs;
+    }
+    lo + arr[lo..hi].partition_point(|x| !is_less(key, x))
+}
+
+/// Elements in the scratch buffer that are not yet merged back.
+///
+/// When dropped (after a merge completes, or when `is_less` panics), it
+/// copies `start..end` to `dest`, so every element is in the slice exactly
+/// once.
+struct MergeHole<T> {
+    start: *mut T,
+    end: *mut T,
+    dest: *mut T,
+}
+
+impl<T> MergeHole<T> {
+    fn len(&self) -> usize {
+        // Safety: `start` and `end` point into the same buffer and
+        // `start <= end`. `T` is never zero-sized here.
+        unsafe { self.end.offset_from(self.start) as usize }
+    }
+}
+
+impl<T> Drop for MergeHole<T> {
+    fn drop(&mut self) {
+        // Safety: the buffer and the slice never overlap.
+        unsafe { ptr::copy_nonoverlapping(self.start, self.dest, self.len()) }
+    }
+}
+
+/// Merge `arr[..mid]` and `arr[mid..]` front to back, with the left run
+/// copied into the buffer. Should be called when `mid <= arr.len() - mid`.
+fn merge_lo<T, F>(arr: &mut [T], mid: usize, state: &mut MergeState<T>, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    state.buf.reserve(mid);
+    let mut min_gallop = state.min_gallop;
+
+    // Safety: the left run is moved into the buffer bitwise, and `MergeHole`
+    // guarantees it is moved back. Invariant: `hole.dest + hole.len() == right`.
+    unsafe {
+        let v = arr.as_mut_ptr();
+        let buf = state.buf.as_mut_ptr();
+        ptr::copy_nonoverlapping(v, buf, mid);
+        let mut hole = MergeHole {
+            start: buf,
+            end: buf.add(mid),
+            dest: v,
+        };
+        let mut right = v.add(mid);
+        let right_end = v.add(len);
+
+        'outer: loop {
+            // Straight merge: one pair at a time, counting consecutive wins.
+            let mut count_a = 0;
+            let mut count_b = 0;
+            loop {
+                if is_less(&*right, &*hole.start) {
+                    ptr::copy_nonoverlapping(right, hole.dest, 1);
+                    hole.dest = hole.dest.add(1);
+                    right = right.add(1);
+                    count_b += 1;
+                    count_a = 0;
+                    if right == right_end {
+                        break 'outer;
+                    }
+                    if count_b >= min_gallop {
+                        break;
+                    }
+                } else {
+                    ptr::copy_nonoverlapping(hole.start, hole.dest, 1);
+                    hole.dest = hole.dest.add(1);
+                    hole.start = hole.start.add(1);
+                    count_a += 1;
+                    count_b = 0;
+                    if hole.start == hole.end {
+                        break 'outer;
+                    }
+                    if count_a >= min_gallop {
+                        break;
+                    }
+                }
+            }
+
+            // Galloping mode: move chunks at once until neither run wins
+            // `MIN_GALLOP` elements in a row.
+            min_gallop += 1;
+            loop {
+                min_gallop -= (min_gallop > 1) as usize;
+
+                let a = slice::from_raw_parts(hole.start, hole.len());
+                count_a = gallop_right(&*right, a, 0, is_less);
+                if count_a > 0 {
+                    ptr::copy_nonoverlapping(hole.start, hole.dest, count_a);
+                    hole.dest = hole.dest.add(count_a);
+                    hole.start = hole.start.add(count_a);
+                    if hole.start == hole.end {
+                        break 'outer;
+                    }
+                }
+                ptr::copy_nonoverlapping(right, hole.dest, 1);
+                hole.dest = hole.dest.add(1);
+                right = right.add(1);
+                if right == right_end {
+                    break 'outer;
+                }
+
+                let b = slice::from_raw_parts(right, right_end.offset_from(right) as usize);
+                count_b = gallop_left(&*hole.start, b, 0, is_less);
+                if count_b > 0 {
+                    // Source and destination may overlap.
+                    ptr::copy(right, hole.dest, count_b);
+                    hole.dest = hole.dest.add(count_b);
+                    right = right.add(count_b);
+                    if right == right_end {
+                        break 'outer;
+                    }
+                }
+                ptr::copy_nonoverlapping(hole.start, hole.dest, 1);
+                hole.dest = hole.dest.add(1);
+                hole.start = hole.start.add(1);
+                if hole.start == hole.end {
+                    break 'outer;
+                }
+
+                if count_a < MIN_GALLOP && count_b < MIN_GALLOP {
+                    break;
+                }
+            }
+            // Penalize leaving galloping mode.
+            min_gallop += 1;
+        }
+        // Left run is moved into the buffer and back, and moved elements of
+        // right run are shifted once.
+        stats::record_moves(2 * mid + right.offset_from(v.add(mid)) as usize);
+        // `hole` is dropped here, moving the rest of left run into place.
+    }
+    state.min_gallop = cmp::max(min_gallop, 1);
+}
+
+/// Merge `arr[..mid]` and `arr[mid..]` back to front, with the right run
+/// copied into the buffer. Should be called when `mid > arr.len() - mid`.
+fn merge_hi<T, F>(arr: &mut [T], mid: usize, state: &mut MergeState<T>, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = arr.len();
+    state.buf.reserve(len - mid);
+    let mut min_gallop = state.min_gallop;
+
+    // Safety: the right run is moved into the buffer bitwise, and `MergeHole`
+    // guarantees it is moved back. `hole.dest` is the end of the unmerged
+    // left run. Invariant: `hole.dest + hole.len() == out`.
+    unsafe {
+        let v = arr.as_mut_ptr();
+        let buf = state.buf.as_mut_ptr();
+        ptr::copy_nonoverlapping(v.add(mid), buf, len - mid);
+        let mut hole = MergeHole {
+            start: buf,
+            end: buf.add(len - mid),
+            dest: v.add(mid),
+        };
+        let left_start = v;
+        let mut out = v.add(len);
+
+        'outer: loop {
+            // Straight merge: one pair at a time, counting consecutive wins.
+            let mut count_a = 0;
+            let mut count_b = 0;
+            loop {
+                if is_less(&*hole.end.sub(1), &*hole.dest.sub(1)) {
+                    hole.dest = hole.dest.sub(1);
+                    out = out.sub(1);
+                    ptr::copy_nonoverlapping(hole.dest, out, 1);
+                    count_a += 1;
+                    count_b = 0;
+                    if hole.dest == left_start {
+                        break 'outer;
+                    }
+                    if count_a >= min_gallop {
+                        break;
+                    }
+                } else {
+                    hole.end = hole.end.sub(1);
+                    out = out.sub(1);
+                    ptr::copy_nonoverlapping(hole.end, out, 1);
+                    count_b += 1;
+                    count_a = 0;
+                    if hole.start == hole.end {
+                        break 'outer;
+                    }
+                    if count_b >= min_gallop {
+                        break;
+                    }
+                }
+            }
+
+            // Galloping mode.
+            min_gallop += 1;
+            loop {
+                min_gallop -= (min_gallop > 1) as usize;
+
+                let a_len = hole.dest.offset_from(left_start) as usize;
+                let a = slice::from_raw_parts(left_start, a_len);
+                count_a = a_len - gallop_right(&*hole.end.sub(1), a, a_len - 1, is_less);
+                if count_a > 0 {
+                    hole.dest = hole.dest.sub(count_a);
+                    out = out.sub(count_a);
+                    // Source and destination may overlap.
+                    ptr::copy(hole.dest, out, count_a);
+                    if hole.dest == left_start {
+                        break 'outer;
+                    }
+                }
+                hole.end = hole.end.sub(1);
+                out = out.sub(1);
+                ptr::copy_nonoverlapping(hole.end, out, 1);
+                if hole.start == hole.end {
+                    break 'outer;
+                }
+
+                let b_len = hole.len();
+                let b = slice::from_raw_parts(hole.start, b_len);
+                count_b = b_len - gallop_left(&*hole.dest.sub(1), b, b_len - 1, is_less);
+                if count_b > 0 {
+                    hole.end = hole.end.sub(count_b);
+                    out = out.sub(count_b);
+                    ptr::copy_nonoverlapping(hole.end, out, count_b);
+                    if hole.start == hole.end {
+                        break 'outer;
+                    }
+                }
+                hole.dest = hole.dest.sub(1);
+                out = out.sub(1);
+                ptr::copy_nonoverlapping(hole.dest, out, 1);
+                if hole.dest == left_start {
+                    break 'outer;
+                }
+
+                if count_a < MIN_GALLOP && count_b < MIN_GALLOP {
+                    break;
+                }
+            }
+            // Penalize leaving galloping mode.
+            min_gallop += 1;
+        }
+        // Right run is moved into the buffer and back, and moved elements of
+        // left run are shifted once.
+        stats::record_moves(2 * (len - mid) + v.add(mid).offset_from(hole.dest) as usize);
+        // `hole` is dropped here, moving the rest of right run into place.
+    }
+    state.min_gallop = cmp::max(min_gallop, 1);
+}
+
+#[cfg(test)]
+mod base {
+    use super::*;
+    base_cases!(timsort);
+    by_cases!(timsort_by, timsort_by_key);
+}
+
+#[cfg(test)]
+mod stability {
+    use super::*;
+    stability_cases!(timsort);
+}
+
+#[cfg(test)]
+mod runs {
+    use super::*;
+
+    #[test]
+    fn min_run() {
+        assert_eq!(min_run_length(63), 63);
+        assert_eq!(min_run_length(64), 32);
+        assert_eq!(min_run_length(65), 33);
+        assert_eq!(min_run_length(2048), 32);
+        assert_eq!(min_run_length(2049), 33);
+    }
+
+    #[test]
+    fn random() {
+        for _ in 0..50 {
+            let len = rand::random::<usize>() % 5000;
+            let mut arr: Vec<_> = (0..len).map(|_| rand::random::<i32>() % 100).collect();
+            let mut res = arr.clone();
+            res.sort();
+            timsort(&mut arr);
+            assert_eq!(arr, res);
+        }
+    }
+
+    #[test]
+    fn nearly_sorted() {
+        // Long ascending and descending runs trigger galloping mode.
+        let mut arr: Vec<_> = (0..3000).chain((0..2000).rev()).chain(1000..4000).collect();
+        for _ in 0..20 {
+            let i = rand::random::<usize>() % arr.len();
+            let j = rand::random::<usize>() % arr.len();
+            arr.swap(i, j);
+        }
+        let mut res = arr.clone();
+        res.sort();
+        timsort(&mut arr);
+        assert_eq!(arr, res);
+    }
+
+    #[test]
+    fn stable_on_large_input() {
+        let mut arr: Vec<_> = (0..10000)
+            .map(|i| (rand::random::<i32>() % 10, i))
+            .collect();
+        let mut res = arr.clone();
+        res.sort_by_key(|t| t.0);
+        timsort_by_key(&mut arr, |t| t.0);
+        assert_eq!(arr, res);
+    }
+}
+
+#[cfg(test)]
+mod panic_safety {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn comparator_panics() {
+        // Elements are neither lost nor duplicated if the comparator panics
+        // in the middle of a galloping merge.
+        let len = 1000;
+        let input: Vec<_> = (0..len)
+            .map(|i| if i % 200 < 100 { i } else { len - i })
+            .collect();
+        let mut expected = input.clone();
+        let mut total = 0;
+        timsort_by(&mut expected, |a, b| {
+            total += 1;
+            a.cmp(b)
+        });
+
+        for limit in &[total / 4, total / 2, total * 3 / 4, total] {
+            let mut arr: Vec<_> = input.iter().copied().map(Box::new).collect();
+            let mut count = 0;
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                timsort_by(&mut arr, |a, b| {
+                    count += 1;
+                    if count == *limit {
+                        panic!("comparator panicked");
+                    }
+                    a.cmp(b)
+                })
+            }));
+            assert!(result.is_err());
+            let mut values: Vec<_> = arr.iter().map(|b| **b).collect();
+            values.sort();
+            assert_eq!(values, expected);
+        }
+    }
 }
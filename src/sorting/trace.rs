@@ -0,0 +1,461 @@
+//! Step-by-step tracing of sorting algorithms.
+//!
+//! Like `SortStats`, sorting algorithms report their steps to a thread-local
+//! tracer, which is activated by `Trace::record`. Element positions are
+//! resolved by address, so only operations on the traced slice itself are
+//! recorded. Comparisons against copies in an auxiliary buffer are not.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::mem;
+
+use crate::sorting::stats;
+
+/// A step of a sorting algorithm on the traced slice.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SortEvent<T> {
+    /// Elements at two positions are compared.
+    Compare(usize, usize),
+    /// Elements at two positions are swapped.
+    Swap(usize, usize),
+    /// A value is written to a position.
+    Write(usize, T),
+    /// The element at a position is chosen as the pivot of a partition.
+    PartitionPivot(usize),
+    /// Two sorted ranges `lo..mid` and `mid..hi` are about to be merged.
+    MergeRange(usize, usize, usize),
+}
+
+/// A recorded sequence of sorting steps.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{heapsort, SortEvent, Trace};
+///
+/// let mut arr = [2, 1];
+/// let trace = Trace::record(&mut arr, heapsort);
+/// assert_eq!(trace.events()[0], SortEvent::Compare(0, 1));
+/// assert_eq!(trace.frames().last().unwrap().array, [1, 2]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Trace<T> {
+    initial: Vec<T>,
+    events: Vec<SortEvent<T>>,
+}
+
+/// State of the traced slice right after an event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame<'a, T> {
+    /// The event leading to this state.
+    pub event: &'a SortEvent<T>,
+    /// Values of the slice after the event.
+    pub array: Vec<T>,
+}
+
+impl<T: Clone + 'static> Trace<T> {
+    /// Run `sort` on `arr` and record every step it takes.
+    pub fn record<F: FnOnce(&mut [T])>(arr: &mut [T], sort: F) -> Trace<T> {
+        let initial = arr.to_vec();
+        let tracer = Tracer {
+            start: arr.as_ptr() as usize,
+            len: arr.len(),
+            size: mem::size_of::<T>(),
+            clone: clone_erased::<T>,
+            events: Vec::new(),
+        };
+        let outer = TRACER.with(|t| t.replace(Some(tracer)));
+        // Restore the outer tracer even if `sort` panics.
+        let guard = Restore(outer);
+        let active = stats::Active::new();
+        sort(arr);
+        drop(active);
+        let tracer = TRACER.with(|t| t.borrow_mut().take());
+        drop(guard);
+
+        let events = tracer
+            .map(|t| t.events)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|event| match event {
+                SortEvent::Compare(i, j) => SortEvent::Compare(i, j),
+                SortEvent::Swap(i, j) => SortEvent::Swap(i, j),
+                SortEvent::Write(i, v) => SortEvent::Write(i, *v.downcast::<T>().unwrap()),
+                SortEvent::PartitionPivot(p) => SortEvent::PartitionPivot(p),
+                SortEvent::MergeRange(lo, mid, hi) => SortEvent::MergeRange(lo, mid, hi),
+            })
+            .collect();
+        Trace { initial, events }
+    }
+}
+
+impl<T: Clone> Trace<T> {
+    /// Values of the slice before sorting.
+    pub fn initial(&self) -> &[T] {
+        &self.initial
+    }
+
+    /// Recorded events in order.
+    pub fn events(&self) -> &[SortEvent<T>] {
+        &self.events
+    }
+
+    /// Replay events on the initial values, one frame per event.
+    pub fn frames(&self) -> Vec<Frame<'_, T>> {
+        let mut array = self.initial.clone();
+        self.events
+            .iter()
+            .map(|event| {
+                match event {
+                    SortEvent::Swap(i, j) => array.swap(*i, *j),
+                    SortEvent::Write(i, v) => array[*i] = v.clone(),
+                    _ => {}
+                }
+                Frame {
+                    event,
+                    array: array.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Run `sort` on `arr` and count writes to the slice.
+///
+/// A swap of two positions counts as two writes, and a swap of a position
+/// with itself as none. Writes to auxiliary buffers are not counted.
+///
+/// ```
+/// use rust_algorithm_club::sorting::{count_writes, cycle_sort, insertion_sort, selection_sort};
+///
+/// let arr = [5, 1, 4, 2, 3];
+/// assert_eq!(count_writes(&mut arr.clone(), insertion_sort), 12);
+/// assert_eq!(count_writes(&mut arr.clone(), selection_sort), 8);
+/// assert_eq!(count_writes(&mut arr.clone(), cycle_sort), 5);
+/// ```
+pub fn count_writes<T, F>(arr: &mut [T], sort: F) -> usize
+where
+    T: Clone + 'static,
+    F: FnOnce(&mut [T]),
+{
+    Trace::record(arr, sort)
+        .events()
+        .iter()
+        .map(|event| match *event {
+            SortEvent::Swap(i, j) if i != j => 2,
+            SortEvent::Write(..) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+impl<T: Copy + Into<i64>> Trace<T> {
+    /// Render frames as a JSON array.
+    ///
+    /// Each frame is an object with the event and values of the slice, e.g.
+    /// `{"event":{"type":"swap","i":0,"j":1},"array":[1,2]}`.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (n, frame) in self.frames().iter().enumerate() {
+            if n > 0 {
+                json.push(',');
+            }
+            let event = match *frame.event {
+                SortEvent::Compare(i, j) => format!(r#"{{"type":"compare","i":{},"j":{}}}"#, i, j),
+                SortEvent::Swap(i, j) => format!(r#"{{"type":"swap","i":{},"j":{}}}"#, i, j),
+                SortEvent::Write(i, v) => {
+                    format!(r#"{{"type":"write","i":{},"value":{}}}"#, i, v.into())
+                }
+                SortEvent::PartitionPivot(p) => format!(r#"{{"type":"pivot","i":{}}}"#, p),
+                SortEvent::MergeRange(lo, mid, hi) => format!(
+                    r#"{{"type":"merge","lo":{},"mid":{},"hi":{}}}"#,
+                    lo, mid, hi
+                ),
+            };
+            let array = frame
+                .array
+                .iter()
+                .map(|v| (*v).into().to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = write!(json, r#"{{"event":{},"array":[{}]}}"#, event, array);
+        }
+        json.push(']');
+        json
+    }
+
+    /// Render frames as plain-text horizontal bar charts, one string per
+    /// frame. Positions involved in the event are marked with `<`.
+    pub fn to_bar_chart(&self) -> Vec<String> {
+        let values = || self.initial.iter().map(|v| (*v).into());
+        let min = values().min().unwrap_or(0);
+        self.frames()
+            .iter()
+            .map(|frame| {
+                let marked: Vec<usize> = match *frame.event {
+                    SortEvent::Compare(i, j) | SortEvent::Swap(i, j) => vec![i, j],
+                    SortEvent::Write(i, _) | SortEvent::PartitionPivot(i) => vec![i],
+                    SortEvent::MergeRange(lo, _, hi) => (lo..hi).collect(),
+                };
+                let mut chart = format!("{:?}\n", frame.event.map_value(|v| (*v).into()));
+                for (i, v) in frame.array.iter().enumerate() {
+                    let v: i64 = (*v).into();
+                    let bar = "#".repeat((v - min + 1) as usize);
+                    let mark = if marked.contains(&i) { " <" } else { "" };
+                    let _ = writeln!(chart, "{:>3} |{} {}{}", i, bar, v, mark);
+                }
+                chart
+            })
+            .collect()
+    }
+}
+
+impl<T> SortEvent<T> {
+    /// Map the value carried by `Write` event.
+    fn map_value<U, F: FnOnce(&T) -> U>(&self, f: F) -> SortEvent<U> {
+        match *self {
+            SortEvent::Compare(i, j) => SortEvent::Compare(i, j),
+            SortEvent::Swap(i, j) => SortEvent::Swap(i, j),
+            SortEvent::Write(i, ref v) => SortEvent::Write(i, f(v)),
+            SortEvent::PartitionPivot(p) => SortEvent::PartitionPivot(p),
+            SortEvent::MergeRange(lo, mid, hi) => SortEvent::MergeRange(lo, mid, hi),
+        }
+    }
+}
+
+/// The active tracer with type-erased element values.
+struct Tracer {
+    /// Address of the traced slice.
+    start: usize,
+    len: usize,
+    /// Size of an element.
+    size: usize,
+    /// Clone the traced element at an address.
+    clone: unsafe fn(*const u8) -> Box<dyn Any>,
+    events: Vec<SortEvent<Box<dyn Any>>>,
+}
+
+impl Tracer {
+    /// Position of a value in the traced slice, if it is in there.
+    fn index_of<T>(&self, value: &T) -> Option<usize> {
+        if mem::size_of::<T>() != self.size || self.size == 0 {
+            return None;
+        }
+        let offset = (value as *const T as usize).checked_sub(self.start)?;
+        let index = offset / self.size;
+        if offset % self.size == 0 && index < self.len {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+thread_local! {
+    static TRACER: RefCell<Option<Tracer>> = const { RefCell::new(None) };
+}
+
+/// Guard restoring the outer tracer.
+struct Restore(Option<Tracer>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        TRACER.with(|t| *t.borrow_mut() = outer);
+    }
+}
+
+/// Safety: `ptr` must point to a valid `T`.
+unsafe fn clone_erased<T: Clone + 'static>(ptr: *const u8) -> Box<dyn Any> {
+    Box::new((*(ptr as *const T)).clone())
+}
+
+/// Run `f` with the active tracer, if any.
+#[inline]
+fn with_tracer<F: FnOnce(&mut Tracer)>(f: F) {
+    if !stats::active() {
+        return;
+    }
+    TRACER.with(|t| {
+        // Skip reentrant calls, e.g. sorting inside `Clone` of an element.
+        if let Ok(mut tracer) = t.try_borrow_mut() {
+            if let Some(tracer) = tracer.as_mut() {
+                f(tracer);
+            }
+        }
+    })
+}
+
+/// Record a comparison between two elements of the traced slice.
+pub(crate) fn compare<T>(a: &T, b: &T) {
+    with_tracer(|t| {
+        if let (Some(i), Some(j)) = (t.index_of(a), t.index_of(b)) {
+            t.events.push(SortEvent::Compare(i, j));
+        }
+    })
+}
+
+/// Record a swap. Should be called before swapping.
+pub(crate) fn swap<T>(arr: &[T], a: usize, b: usize) {
+    with_tracer(|t| {
+        if let (Some(i), Some(j)) = (t.index_of(&arr[a]), t.index_of(&arr[b])) {
+            t.events.push(SortEvent::Swap(i, j));
+        }
+    })
+}
+
+/// Record current values of `arr` as writes, after they are written in bulk.
+pub(crate) fn writes<T>(arr: &[T]) {
+    with_tracer(|t| {
+        for value in arr {
+            if let Some(i) = t.index_of(value) {
+                // Safety: `value` is a valid reference into the traced
+                // slice, which is of the type `clone` expects.
+                let value = unsafe { (t.clone)(value as *const T as *const u8) };
+                t.events.push(SortEvent::Write(i, value));
+            }
+        }
+    })
+}
+
+/// Record the pivot of a partition.
+pub(crate) fn pivot<T>(pivot: &T) {
+    with_tracer(|t| {
+        if let Some(p) = t.index_of(pivot) {
+            t.events.push(SortEvent::PartitionPivot(p));
+        }
+    })
+}
+
+/// Record a merge of `arr[..mid]` and `arr[mid..]`.
+pub(crate) fn merge<T>(arr: &[T], mid: usize) {
+    with_tracer(|t| {
+        if let Some(lo) = arr.first().and_then(|v| t.index_of(v)) {
+            t.events
+                .push(SortEvent::MergeRange(lo, lo + mid, lo + arr.len()));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sorting::*;
+
+    fn assert_replay(trace: &Trace<i32>, res: &[i32]) {
+        assert_eq!(trace.frames().last().unwrap().array, res);
+    }
+
+    #[test]
+    fn heapsort_events() {
+        let mut arr = [1, 3, 2];
+        let trace = Trace::record(&mut arr, heapsort);
+        assert_eq!(
+            trace.events(),
+            [
+                // Heapify.
+                SortEvent::Compare(1, 2),
+                SortEvent::Compare(0, 1),
+                SortEvent::Swap(0, 1),
+                // Sort.
+                SortEvent::Swap(2, 0),
+                SortEvent::Compare(0, 1),
+                SortEvent::Swap(1, 0),
+            ]
+        );
+        assert_eq!(trace.initial(), [1, 3, 2]);
+        assert_replay(&trace, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn quicksort_hoare_pivots() {
+        let mut arr = [3, 1, 2];
+        let trace = Trace::record(&mut arr, quicksort_hoare);
+        assert_eq!(trace.events()[0], SortEvent::PartitionPivot(0));
+        assert!(trace.events().contains(&SortEvent::Swap(0, 2)));
+        assert_replay(&trace, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn mergesort_bottom_up_ranges() {
+        let mut arr = [4, 3, 2, 1];
+        let trace = Trace::record(&mut arr, mergesort_bottom_up);
+        let ranges: Vec<_> = trace
+            .events()
+            .iter()
+            .filter(|e| matches!(e, SortEvent::MergeRange(..)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                SortEvent::MergeRange(0, 1, 2),
+                SortEvent::MergeRange(2, 3, 4),
+                SortEvent::MergeRange(0, 2, 4),
+            ]
+        );
+        assert_eq!(trace.events()[1], SortEvent::Write(0, 3));
+        assert_replay(&trace, &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn replay_every_sort() {
+        let algos: &[fn(&mut [i32])] = &[
+            bubble_sort,
+            insertion_sort,
+            binary_insertion_sort,
+            selection_sort,
+            cycle_sort,
+            shellsort,
+            heapsort,
+            mergesort,
+            mergesort_bottom_up,
+            quicksort,
+            quicksort_3way,
+            quicksort_hoare,
+            timsort,
+            introsort,
+            pdqsort,
+        ];
+        let input: Vec<_> = (0..300).map(|_| rand::random::<i32>() % 50).collect();
+        let mut res = input.clone();
+        res.sort();
+        for algo in algos {
+            let mut arr = input.clone();
+            let trace = Trace::record(&mut arr, algo);
+            assert_replay(&trace, &res);
+        }
+    }
+
+    #[test]
+    fn not_tracing_other_slices() {
+        let mut arr = [2, 1];
+        let mut other = [2, 1];
+        let trace = Trace::record(&mut arr, |_| heapsort(&mut other));
+        assert!(trace.events().is_empty());
+    }
+
+    #[test]
+    fn json() {
+        let mut arr = [2, 1];
+        let trace = Trace::record(&mut arr, heapsort);
+        assert_eq!(
+            trace.to_json(),
+            concat!(
+                r#"[{"event":{"type":"compare","i":0,"j":1},"array":[2,1]},"#,
+                r#"{"event":{"type":"swap","i":1,"j":0},"array":[1,2]}]"#,
+            )
+        );
+    }
+
+    #[test]
+    fn bar_chart() {
+        let mut arr = [3, 1, 2];
+        let trace = Trace::record(&mut arr, insertion_sort);
+        let frames = trace.to_bar_chart();
+        assert_eq!(frames.len(), trace.events().len());
+        assert_eq!(
+            frames[1],
+            "Swap(0, 1)\n  0 |# 1 <\n  1 |### 3 <\n  2 |## 2\n"
+        );
+    }
+}